@@ -0,0 +1,125 @@
+use std::{
+    ops::ControlFlow,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use server::{Error, Input, Output, Reply, Scanner};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::{Feed, ScannerBuilder};
+
+// the master answers commands over a blocking channel and ending it joins
+// threads, both are kept off the runtime's worker threads
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|_| Error::Terminated)?
+}
+
+fn release(scanner: Scanner) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn_blocking(move || drop(scanner));
+        }
+        Err(_) => drop(scanner),
+    }
+}
+
+// `Scanner` for async code, dropping the last clone ends the scan
+pub struct AsyncScanner {
+    scanner: Option<Scanner>,
+}
+
+impl AsyncScanner {
+    fn scanner(&self) -> Scanner {
+        self.scanner.clone().expect("scanner is only taken on drop")
+    }
+    pub async fn command(&self, input: Input) -> Result<Reply, Error> {
+        let scanner = self.scanner();
+        blocking(move || scanner.command(input)).await
+    }
+    pub async fn join(&self) -> Result<(), Error> {
+        let scanner = self.scanner();
+        blocking(move || scanner.join()).await
+    }
+    pub async fn shutdown(&self, milis: usize) -> Result<(), Error> {
+        let scanner = self.scanner();
+        blocking(move || scanner.shutdown(milis)).await
+    }
+}
+
+impl Clone for AsyncScanner {
+    fn clone(&self) -> Self {
+        AsyncScanner {
+            scanner: self.scanner.clone(),
+        }
+    }
+}
+
+impl Drop for AsyncScanner {
+    fn drop(&mut self) {
+        if let Some(scanner) = self.scanner.take() {
+            release(scanner);
+        }
+    }
+}
+
+// `ScanStream` for async code
+pub struct AsyncScanStream {
+    feed: Feed<AsyncScanner>,
+    rx: UnboundedReceiver<Output>,
+}
+
+impl AsyncScanStream {
+    pub fn scanner(&self) -> Option<&AsyncScanner> {
+        self.feed.scanner.as_ref()
+    }
+}
+
+impl Stream for AsyncScanStream {
+    type Item = Output;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Output>> {
+        let this = self.get_mut();
+        while this.feed.scanner.is_some() {
+            let output = match this.rx.poll_recv(cx) {
+                Poll::Ready(output) => output,
+                Poll::Pending => return Poll::Pending,
+            };
+            if let ControlFlow::Break(item) = this.feed.take(output) {
+                return Poll::Ready(item);
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+impl ScannerBuilder {
+    pub async fn build_async<O: Fn(Output) + Send + 'static>(
+        self,
+        o: O,
+    ) -> Result<AsyncScanner, Error> {
+        let scanner = blocking(move || self.build(o)).await?;
+        Ok(AsyncScanner {
+            scanner: Some(scanner),
+        })
+    }
+    pub async fn stream_async(self) -> Result<AsyncScanStream, Error> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let empty = self.scans.is_empty();
+        let scanner = self
+            .build_async(move |o| {
+                let _ = tx.send(o);
+            })
+            .await?;
+        // dropping releases the scanner off the runtime, see `release`
+        let feed = Feed::new(scanner, empty, |scanner| {
+            drop(scanner);
+            Ok(())
+        })?;
+        Ok(AsyncScanStream { feed, rx })
+    }
+}
@@ -1,4 +1,15 @@
-use server::{Input, Output, Scanner};
+#[cfg(feature = "tokio")]
+mod asynchronous;
+mod report;
+
+use std::{net::IpAddr, ops::ControlFlow, sync::Arc};
+
+use crossbeam::channel::Receiver;
+use server::{Connector, Error, Input, JobConfig, Output, Prober, Scanner, SocketProber};
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::{AsyncScanStream, AsyncScanner};
+pub use report::{HostReport, PortSets, ReportBuilder, ReportConfig, ScanRange, ScanReport};
 
 #[derive(Default)]
 pub struct ScannerBuilder {
@@ -7,7 +18,22 @@ pub struct ScannerBuilder {
     udp_timeout: Option<usize>,
     attemps: Option<usize>,
     stale: Option<bool>,
-    scans: Vec<(String, u16, u16, bool)>,
+    audit_log: Option<String>,
+    host_limit: Option<usize>,
+    subnet_limit: Option<usize>,
+    resolve_all: Option<bool>,
+    dns_ttl: Option<usize>,
+    reverse_dns: Option<bool>,
+    rdns_timeout: Option<usize>,
+    rdns_concurrency: Option<usize>,
+    source_ip: Option<IpAddr>,
+    source_port: Option<u16>,
+    interface: Option<String>,
+    netns: Option<String>,
+    proxy: Option<String>,
+    connector: Option<Arc<dyn Connector>>,
+    prober: Option<Arc<dyn Prober>>,
+    scans: Vec<(String, u16, u16, bool, JobConfig)>,
 }
 
 impl ScannerBuilder {
@@ -21,14 +47,19 @@ impl ScannerBuilder {
         s.attemps = Some(value);
         s
     }
-    pub fn scan_tcp(self, host: String, from: u16, to: u16) -> Self {
+    pub fn prober<P: Prober + 'static>(self, value: P) -> Self {
+        let mut s = self;
+        s.prober = Some(Arc::new(value));
+        s
+    }
+    pub fn scan_tcp(self, host: String, from: u16, to: u16, config: JobConfig) -> Self {
         let mut s = self;
-        s.scans.push((host, from, to, true));
+        s.scans.push((host, from, to, true, config));
         s
     }
-    pub fn scan_udp(self, host: String, from: u16, to: u16) -> Self {
+    pub fn scan_udp(self, host: String, from: u16, to: u16, config: JobConfig) -> Self {
         let mut s = self;
-        s.scans.push((host, from, to, false));
+        s.scans.push((host, from, to, false, config));
         s
     }
     pub fn tcp_timeout(self, value: usize) -> Self {
@@ -46,53 +77,258 @@ impl ScannerBuilder {
         s.stale = Some(value);
         s
     }
-    fn config(&self, scanner: &Scanner) {
+    pub fn audit_log(self, path: String) -> Self {
+        let mut s = self;
+        s.audit_log = Some(path);
+        s
+    }
+    pub fn host_limit(self, value: usize) -> Self {
+        let mut s = self;
+        s.host_limit = Some(value);
+        s
+    }
+    pub fn subnet_limit(self, value: usize) -> Self {
+        let mut s = self;
+        s.subnet_limit = Some(value);
+        s
+    }
+    pub fn resolve_all(self, value: bool) -> Self {
+        let mut s = self;
+        s.resolve_all = Some(value);
+        s
+    }
+    // seconds, applies to every name, record TTLs are not available
+    pub fn dns_ttl(self, value: usize) -> Self {
+        let mut s = self;
+        s.dns_ttl = Some(value);
+        s
+    }
+    pub fn reverse_dns(self, value: bool) -> Self {
+        let mut s = self;
+        s.reverse_dns = Some(value);
+        s
+    }
+    pub fn rdns_timeout(self, value: usize) -> Self {
+        let mut s = self;
+        s.rdns_timeout = Some(value);
+        s
+    }
+    pub fn rdns_concurrency(self, value: usize) -> Self {
+        let mut s = self;
+        s.rdns_concurrency = Some(value);
+        s
+    }
+    pub fn source_ip(self, value: IpAddr) -> Self {
+        let mut s = self;
+        s.source_ip = Some(value);
+        s
+    }
+    pub fn source_port(self, value: u16) -> Self {
+        let mut s = self;
+        s.source_port = Some(value);
+        s
+    }
+    pub fn interface(self, value: String) -> Self {
+        let mut s = self;
+        s.interface = Some(value);
+        s
+    }
+    pub fn netns(self, value: String) -> Self {
+        let mut s = self;
+        s.netns = Some(value);
+        s
+    }
+    pub fn proxy(self, value: String) -> Self {
+        let mut s = self;
+        s.proxy = Some(value);
+        s
+    }
+    // how TCP probes reach their targets, takes precedence over `proxy`
+    pub fn connector<C: Connector + 'static>(self, value: C) -> Self {
+        let mut s = self;
+        s.connector = Some(Arc::new(value));
+        s
+    }
+    fn config(&self, scanner: &Scanner) -> Result<(), Error> {
         if let Some(val) = self.attemps {
-            scanner.command(Input::Attmpts(val));
+            scanner.command(Input::Attmpts(val))?;
         }
         if let Some(val) = self.stale {
-            scanner.command(Input::Stale(val));
+            scanner.command(Input::Stale(val))?;
         }
         if let Some(val) = self.thread_count {
-            scanner.command(Input::Threads(val));
+            scanner.command(Input::Threads(val))?;
         }
         if let Some(val) = self.tcp_timeout {
-            scanner.command(Input::TcpTimeout(val));
+            scanner.command(Input::TcpTimeout(val))?;
         }
         if let Some(val) = self.udp_timeout {
-            scanner.command(Input::UdpTimeout(val));
+            scanner.command(Input::UdpTimeout(val))?;
+        }
+        if let Some(val) = self.host_limit {
+            scanner.command(Input::HostLimit(val))?;
+        }
+        if let Some(val) = self.subnet_limit {
+            scanner.command(Input::SubnetLimit(val))?;
+        }
+        if let Some(val) = self.resolve_all {
+            scanner.command(Input::ResolveAll(val))?;
+        }
+        if let Some(val) = self.dns_ttl {
+            scanner.command(Input::DnsTtl(val))?;
+        }
+        if let Some(val) = self.reverse_dns {
+            scanner.command(Input::ReverseDns(val))?;
+        }
+        if let Some(val) = self.rdns_timeout {
+            scanner.command(Input::RdnsTimeout(val))?;
+        }
+        if let Some(val) = self.rdns_concurrency {
+            scanner.command(Input::RdnsConcurrency(val))?;
         }
+        if let Some(val) = self.source_ip {
+            scanner.command(Input::SourceIp(Some(val)))?;
+        }
+        if let Some(val) = self.source_port {
+            scanner.command(Input::SourcePort(Some(val)))?;
+        }
+        if let Some(val) = self.interface.clone() {
+            scanner.command(Input::Interface(Some(val)))?;
+        }
+        if let Some(val) = self.netns.clone() {
+            scanner.command(Input::Netns(Some(val)))?;
+        }
+        if let Some(val) = self.proxy.clone() {
+            scanner.command(Input::Proxy(Some(val)))?;
+        }
+        if let Some(val) = self.connector.clone() {
+            scanner.command(Input::Connector(val))?;
+        }
+        if let Some(val) = self.audit_log.clone() {
+            scanner.command(Input::AuditLog(val))?;
+        }
+        Ok(())
     }
-    fn enqueue_jobs(&mut self, scanner: &Scanner) {
-        for (host, from, to, is_tcp) in self.scans.drain(..) {
+    fn enqueue_jobs(&mut self, scanner: &Scanner) -> Result<(), Error> {
+        // paused while queueing, otherwise the first jobs may be done and
+        // reported `Idle` before the last one is queued
+        scanner.command(Input::Stop)?;
+        for (host, from, to, is_tcp, config) in self.scans.drain(..) {
             if is_tcp {
-                scanner.command(Input::TcpRange(host, from, to));
+                scanner.command(Input::TcpRange(host, from, to, config))?;
             } else {
-                scanner.command(Input::UdpRange(host, from, to));
+                scanner.command(Input::UdpRange(host, from, to, config))?;
             }
         }
+        scanner.command(Input::Cont)?;
+        Ok(())
+    }
+    pub fn build<O: Fn(Output) + Send + 'static>(self, o: O) -> Result<Scanner, Error> {
+        Ok(self.start(o)?.0)
     }
-    pub fn build<O: Fn(Output) + Send + 'static>(self, o: O) -> Scanner {
-        let scanner = Scanner::new(o);
+    // the scanner along with the settings it runs with
+    fn start<O: Fn(Output) + Send + 'static>(self, o: O) -> Result<(Scanner, ReportConfig), Error> {
+        let prober = self.prober.clone().unwrap_or(Arc::new(SocketProber));
+        let scanner = Scanner::with_prober(prober, o);
         let mut s = self;
-        s.config(&scanner);
-        s.enqueue_jobs(&scanner);
-        scanner
+        // on error the scanner is dropped, which ends it. nothing is
+        // queued unless every setting was accepted
+        s.config(&scanner)?;
+        let config = s.report_config(scanner.settings()?);
+        s.enqueue_jobs(&scanner)?;
+        Ok((scanner, config))
+    }
+    pub fn stream(self) -> Result<ScanStream, Error> {
+        Ok(self.start_stream()?.0)
     }
-    pub fn run(self) -> Vec<Output> {
-        let mut output = vec![];
+    fn start_stream(self) -> Result<(ScanStream, ReportConfig), Error> {
         let (tx, rx) = crossbeam::channel::unbounded();
-        self.build(move |o| {
+        let empty = self.scans.is_empty();
+        let (scanner, config) = self.start(move |o| {
             let _ = tx.send(o);
-        });
-        loop {
-            let o = rx.recv().unwrap();
-            if o == Output::Idle {
-                break;
-            } else {
-                output.push(o)
+        })?;
+        let feed = Feed::new(scanner, empty, |scanner| scanner.shutdown(0))?;
+        Ok((ScanStream { feed, rx }, config))
+    }
+    pub fn run(self) -> Result<Vec<Output>, Error> {
+        Ok(self.stream()?.collect())
+    }
+}
+
+// scan results and errors as they arrive, ends once every job is done.
+// dropping it early ends the scan
+pub struct ScanStream {
+    feed: Feed<Scanner>,
+    rx: Receiver<Output>,
+}
+
+impl ScanStream {
+    pub fn scanner(&self) -> Option<&Scanner> {
+        self.feed.scanner.as_ref()
+    }
+}
+
+impl Iterator for ScanStream {
+    type Item = Output;
+    fn next(&mut self) -> Option<Output> {
+        while self.feed.scanner.is_some() {
+            if let ControlFlow::Break(item) = self.feed.take(self.rx.recv().ok()) {
+                return item;
+            }
+        }
+        None
+    }
+}
+
+// what `ScanStream` and `AsyncScanStream` share: the outputs they yield,
+// and the scanner they hold until the scan is over
+struct Feed<S> {
+    scanner: Option<S>,
+    finish: fn(S) -> Result<(), Error>, // tears the scanner down
+}
+
+impl<S> Feed<S> {
+    fn new(scanner: S, empty: bool, finish: fn(S) -> Result<(), Error>) -> Result<Self, Error> {
+        let mut feed = Feed {
+            scanner: Some(scanner),
+            finish,
+        };
+        // nothing to wait for, no `Idle` would ever come
+        if empty {
+            feed.finish()?;
+        }
+        Ok(feed)
+    }
+    // with the error the master ended with if any
+    fn finish(&mut self) -> Result<(), Error> {
+        match self.scanner.take() {
+            Some(scanner) => (self.finish)(scanner),
+            None => Ok(()),
+        }
+    }
+    // what the stream makes of the master's next output, `None` once the
+    // master is gone. breaks with the stream's next item
+    fn take(&mut self, output: Option<Output>) -> ControlFlow<Option<Output>> {
+        match output {
+            Some(Output::Idle) => {
+                let _ = self.finish();
+                ControlFlow::Break(None)
+            }
+            Some(o) if is_result(&o) => ControlFlow::Break(Some(o)),
+            Some(_) => ControlFlow::Continue(()),
+            None => {
+                // the master is gone without reporting the end of the scan
+                let err = self.finish().err().unwrap_or(Error::Terminated);
+                ControlFlow::Break(Some(Output::Error(err)))
             }
         }
-        output
     }
 }
+
+// what the streams yield, job, pause and settings events are left out
+fn is_result(output: &Output) -> bool {
+    matches!(
+        output,
+        Output::TcpScan(_) | Output::UdpScan(_) | Output::Error(_)
+    )
+}
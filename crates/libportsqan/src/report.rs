@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use server::{Output, PortState, Prober, ScanResult, Settings, SocketProber};
+
+use crate::ScannerBuilder;
+
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PortSets {
+    pub open: BTreeSet<u16>,
+    pub closed: BTreeSet<u16>,
+    pub filtered: BTreeSet<u16>, // no answer
+}
+
+impl PortSets {
+    fn insert(&mut self, port: u16, state: PortState) {
+        match state {
+            PortState::Open => self.open.insert(port),
+            PortState::Closed => self.closed.insert(port),
+            PortState::Unreachable => self.filtered.insert(port),
+        };
+    }
+}
+
+// one address of a scanned host, a name resolving to several
+// addresses gets one per address
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct HostReport {
+    pub host: String,
+    pub address: IpAddr,      // unspecified when resolved by the proxy
+    pub name: Option<String>, // reverse DNS
+    pub tcp: PortSets,
+    pub udp: PortSets,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ScanRange {
+    pub host: String,
+    pub protocol: String,
+    pub from: u16,
+    pub to: u16,
+    // the job's own settings or the scanner's
+    pub timeout: usize, // miliseconds
+    pub attemps: usize,
+    pub priority: usize,
+}
+
+// the settings the scan ran with, defaults included
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ReportConfig {
+    pub thread_count: usize,
+    pub tcp_timeout: usize, // miliseconds
+    pub udp_timeout: usize, // miliseconds
+    pub attemps: usize,
+    pub stale: bool,
+    pub host_limit: usize,
+    pub subnet_limit: usize,
+    pub resolve_all: bool,
+    pub dns_ttl: usize, // seconds
+    pub reverse_dns: bool,
+    pub rdns_timeout: usize, // miliseconds
+    pub rdns_concurrency: usize,
+    pub source_ip: Option<IpAddr>, // `None` when left to the kernel
+    pub source_port: Option<u16>,
+    pub interface: Option<String>,
+    pub netns: Option<String>,
+    pub connector: String, // `none`, the proxy url without the password or `custom`
+    pub prober: String,    // `sockets`, `simulated` or `custom`
+    pub audit_log: Option<String>,
+    pub scans: Vec<ScanRange>,
+}
+
+#[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub start: u64,    // miliseconds since the epoch
+    pub end: u64,      // miliseconds since the epoch
+    pub duration: u64, // miliseconds
+    pub probes: usize,
+    pub config: ReportConfig,
+    pub hosts: Vec<HostReport>,
+    pub errors: Vec<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// collects the outputs of a scan into a `ScanReport`
+pub struct ReportBuilder {
+    report: ScanReport,
+    hosts: BTreeMap<(String, IpAddr), HostReport>,
+}
+
+impl ReportBuilder {
+    pub fn new(config: ReportConfig) -> ReportBuilder {
+        ReportBuilder {
+            report: ScanReport {
+                start: now(),
+                config,
+                ..Default::default()
+            },
+            hosts: BTreeMap::new(),
+        }
+    }
+    fn host(&mut self, result: &ScanResult) -> &mut HostReport {
+        let key = (result.host.clone(), result.address.ip());
+        let host = self.hosts.entry(key).or_insert_with(|| HostReport {
+            host: result.host.clone(),
+            address: result.address.ip(),
+            name: None,
+            tcp: PortSets::default(),
+            udp: PortSets::default(),
+        });
+        if host.name.is_none() {
+            host.name = result.name.clone();
+        }
+        host
+    }
+    pub fn add(&mut self, output: &Output) {
+        match output {
+            Output::TcpScan(result) => {
+                self.report.probes += 1;
+                let port = result.address.port();
+                self.host(result).tcp.insert(port, result.state);
+            }
+            Output::UdpScan(result) => {
+                self.report.probes += 1;
+                let port = result.address.port();
+                self.host(result).udp.insert(port, result.state);
+            }
+            Output::Error(err) => self.report.errors.push(err.to_string()),
+            _ => {}
+        }
+    }
+    pub fn finish(self) -> ScanReport {
+        let mut report = self.report;
+        report.end = now();
+        report.duration = report.end.saturating_sub(report.start);
+        report.hosts = self.hosts.into_values().collect();
+        report
+    }
+}
+
+impl ScannerBuilder {
+    // `settings` are those of the scanner the builder made
+    pub(crate) fn report_config(&self, settings: Settings) -> ReportConfig {
+        let prober = match &self.prober {
+            Some(prober) => prober.describe(),
+            None => SocketProber.describe(),
+        };
+        let scans = self
+            .scans
+            .iter()
+            .map(|(host, from, to, is_tcp, config)| {
+                let timeout = if *is_tcp {
+                    settings.tcp_timeout
+                } else {
+                    settings.udp_timeout
+                };
+                ScanRange {
+                    host: host.clone(),
+                    protocol: if *is_tcp { "tcp" } else { "udp" }.to_owned(),
+                    from: *from,
+                    to: *to,
+                    timeout: config.timeout.unwrap_or(timeout),
+                    attemps: config.attemps.unwrap_or(settings.attemps),
+                    priority: config.priority,
+                }
+            })
+            .collect();
+        ReportConfig {
+            thread_count: settings.thread_count,
+            tcp_timeout: settings.tcp_timeout,
+            udp_timeout: settings.udp_timeout,
+            attemps: settings.attemps,
+            stale: settings.stale,
+            host_limit: settings.host_limit,
+            subnet_limit: settings.subnet_limit,
+            resolve_all: settings.resolve_all,
+            dns_ttl: settings.dns_ttl,
+            reverse_dns: settings.reverse_dns,
+            rdns_timeout: settings.rdns_timeout,
+            rdns_concurrency: settings.rdns_concurrency,
+            source_ip: settings.source_ip,
+            source_port: settings.source_port,
+            interface: settings.interface,
+            netns: settings.netns,
+            connector: settings.connector,
+            prober,
+            audit_log: self.audit_log.clone(),
+            scans,
+        }
+    }
+    // like `run`, grouped by host
+    pub fn report(self) -> Result<ScanReport, server::Error> {
+        let (stream, config) = self.start_stream()?;
+        let mut report = ReportBuilder::new(config);
+        for output in stream {
+            report.add(&output);
+        }
+        Ok(report.finish())
+    }
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "tokio")]
+
+use std::{
+    future::poll_fn,
+    net::{IpAddr, Ipv4Addr},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures_core::Stream;
+use libportsqan::{AsyncScanStream, ScannerBuilder};
+use server::{
+    Input, JobConfig, Outcome, Output, PortState, Probe, Prober, Protocol, SimulatedNetwork,
+};
+use tokio::sync::mpsc;
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+fn network() -> SimulatedNetwork {
+    SimulatedNetwork::default()
+        .port(LOCALHOST, Protocol::Tcp, 2, PortState::Open)
+        .port(LOCALHOST, Protocol::Tcp, 4, PortState::Open)
+}
+
+async fn next(stream: &mut AsyncScanStream) -> Option<Output> {
+    poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+fn open_ports(outputs: &[Output]) -> Vec<u16> {
+    let mut ports = outputs
+        .iter()
+        .filter_map(|output| match output {
+            Output::TcpScan(result) if result.state == PortState::Open => {
+                Some(result.address.port())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    ports.sort_unstable();
+    ports
+}
+
+#[tokio::test]
+async fn command_queues_a_scan() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let scanner = ScannerBuilder::default()
+        .prober(network())
+        .build_async(move |output| {
+            let _ = tx.send(output);
+        })
+        .await
+        .unwrap();
+    let input = Input::TcpRange("127.0.0.1".to_owned(), 1, 5, JobConfig::default());
+    scanner.command(input).await.unwrap();
+    let mut outputs = vec![];
+    loop {
+        match rx.recv().await.unwrap() {
+            Output::JobCompleted(_) => break,
+            output => outputs.push(output),
+        }
+    }
+    assert_eq!(open_ports(&outputs), [2, 4]);
+}
+
+#[tokio::test]
+async fn stream_ends_once_the_scanner_is_idle() {
+    let mut stream = ScannerBuilder::default()
+        .prober(network())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 5, JobConfig::default())
+        .stream_async()
+        .await
+        .unwrap();
+    let mut outputs = vec![];
+    while let Some(output) = next(&mut stream).await {
+        outputs.push(output);
+    }
+    // only the results, the job events are left out
+    assert_eq!(outputs.len(), 5);
+    assert_eq!(open_ports(&outputs), [2, 4]);
+    assert!(stream.scanner().is_none());
+    assert!(next(&mut stream).await.is_none());
+}
+
+#[tokio::test]
+async fn stream_without_scans_ends_right_away() {
+    let mut stream = ScannerBuilder::default()
+        .prober(network())
+        .stream_async()
+        .await
+        .unwrap();
+    assert!(next(&mut stream).await.is_none());
+}
+
+// a slow simulated network that counts its probes
+struct Slow {
+    network: SimulatedNetwork,
+    probes: AtomicUsize,
+}
+
+impl Prober for Slow {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        self.probes.fetch_add(1, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(5));
+        self.network.tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        self.network.udp(probe)
+    }
+}
+
+#[tokio::test]
+async fn dropping_the_stream_ends_the_scan() {
+    let prober = Arc::new(Slow {
+        network: network(),
+        probes: AtomicUsize::new(0),
+    });
+    let mut stream = ScannerBuilder::default()
+        .prober(prober.clone())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 1000, JobConfig::default())
+        .stream_async()
+        .await
+        .unwrap();
+    assert!(next(&mut stream).await.is_some());
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let probes = prober.probes.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(prober.probes.load(Ordering::SeqCst), probes);
+    assert!(probes < 100);
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeSet,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+
+use libportsqan::{PortSets, ReportBuilder, ReportConfig, ScannerBuilder};
+use server::{
+    Cancellation, Connector, Error, JobConfig, Outcome, Output, PortState, Probe, Prober, Protocol,
+    ScanResult, SimulatedNetwork, Source,
+};
+
+const FIRST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+const SECOND: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+fn network() -> SimulatedNetwork {
+    SimulatedNetwork::default()
+        .port(FIRST, Protocol::Tcp, 2, PortState::Open)
+        .port(FIRST, Protocol::Tcp, 3, PortState::Unreachable)
+        .port(FIRST, Protocol::Udp, 2, PortState::Open)
+        .port(SECOND, Protocol::Tcp, 4, PortState::Open)
+}
+
+fn set(ports: &[u16]) -> BTreeSet<u16> {
+    ports.iter().copied().collect()
+}
+
+#[test]
+fn results_are_grouped_by_host_and_address() {
+    let report = ScannerBuilder::default()
+        .prober(network())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 4, JobConfig::default())
+        .scan_udp("127.0.0.1".to_owned(), 1, 2, JobConfig::default())
+        .scan_tcp("127.0.0.2".to_owned(), 3, 5, JobConfig::default())
+        .report()
+        .unwrap();
+    assert_eq!(report.probes, 9);
+    assert!(report.errors.is_empty());
+    let hosts = report
+        .hosts
+        .iter()
+        .map(|host| (host.host.as_str(), host.address))
+        .collect::<Vec<_>>();
+    assert_eq!(hosts, [("127.0.0.1", FIRST), ("127.0.0.2", SECOND)]);
+    assert_eq!(
+        report.hosts[0].tcp,
+        PortSets {
+            open: set(&[2]),
+            closed: set(&[1, 4]),
+            filtered: set(&[3]),
+        }
+    );
+    assert_eq!(report.hosts[0].udp.open, set(&[2]));
+    assert_eq!(report.hosts[0].udp.closed, set(&[1]));
+    assert_eq!(report.hosts[1].tcp.open, set(&[4]));
+    assert_eq!(report.hosts[1].tcp.closed, set(&[3, 5]));
+    assert_eq!(report.hosts[1].udp, PortSets::default());
+}
+
+fn result(host: &str, port: u16, name: Option<&str>) -> Output {
+    Output::TcpScan(ScanResult {
+        host: host.to_owned(),
+        address: SocketAddr::new(FIRST, port),
+        name: name.map(str::to_owned),
+        state: PortState::Open,
+        attemps: 1,
+        latency: None,
+        error: None,
+        time: SystemTime::now(),
+    })
+}
+
+#[test]
+fn names_merge_into_their_host() {
+    let mut report = ReportBuilder::new(ReportConfig::default());
+    // the reverse lookup finishes while the host is being scanned
+    report.add(&result("localhost", 1, None));
+    report.add(&result("localhost", 2, Some("localhost.localdomain")));
+    report.add(&result("localhost", 3, None));
+    // the same address under another name is another host
+    report.add(&result("127.0.0.1", 4, None));
+    let report = report.finish();
+    assert_eq!(report.hosts.len(), 2);
+    let named = report.hosts.iter().find(|h| h.host == "localhost").unwrap();
+    assert_eq!(named.name.as_deref(), Some("localhost.localdomain"));
+    assert_eq!(named.tcp.open, set(&[1, 2, 3]));
+    let literal = report.hosts.iter().find(|h| h.host == "127.0.0.1").unwrap();
+    assert_eq!(literal.name, None);
+}
+
+#[test]
+fn errors_are_collected_and_other_outputs_ignored() {
+    let mut report = ReportBuilder::new(ReportConfig::default());
+    report.add(&Output::Error(Error::Resolve("host.invalid".to_owned())));
+    report.add(&Output::Idle);
+    report.add(&Output::Paused);
+    report.add(&result("127.0.0.1", 1, None));
+    let report = report.finish();
+    assert_eq!(report.errors, ["failed to resolve host.invalid"]);
+    assert_eq!(report.probes, 1);
+}
+
+struct PanicOn(u16);
+
+impl Prober for PanicOn {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        if probe.address.port() == self.0 {
+            panic!("probe of port {}", self.0);
+        }
+        network().tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        network().udp(probe)
+    }
+}
+
+#[test]
+fn failed_probes_are_reported_as_errors() {
+    let report = ScannerBuilder::default()
+        .prober(PanicOn(3))
+        .scan_tcp("127.0.0.1".to_owned(), 1, 4, JobConfig::default())
+        .report()
+        .unwrap();
+    assert_eq!(report.probes, 3);
+    assert_eq!(report.errors, ["Tcp probe of 127.0.0.1:3 has failed"]);
+}
+
+#[test]
+fn config_records_the_defaults() {
+    let report = ScannerBuilder::default()
+        .prober(network())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 2, JobConfig::default())
+        .report()
+        .unwrap();
+    let config = report.config;
+    assert_eq!(config.thread_count, 1);
+    assert_eq!(config.tcp_timeout, 500);
+    assert_eq!(config.udp_timeout, 500);
+    assert_eq!(config.attemps, 1);
+    assert!(config.stale);
+    assert!(!config.reverse_dns);
+    assert_eq!(config.source_ip, None);
+    assert_eq!(config.connector, "none");
+    assert_eq!(config.prober, "simulated");
+    assert_eq!(config.scans[0].timeout, 500);
+    assert_eq!(config.scans[0].attemps, 1);
+}
+
+struct Custom;
+
+impl Connector for Custom {
+    fn connect(
+        &self,
+        _host: &str,
+        _address: SocketAddr,
+        _timeout: Duration,
+        _source: &Source,
+        _cancel: &Cancellation,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn config_records_the_overrides() {
+    let job = JobConfig::default().timeout(20);
+    let report = ScannerBuilder::default()
+        .prober(network())
+        .thread_count(2)
+        .tcp_timeout(50)
+        .udp_timeout(60)
+        .attemps(3)
+        .connector(Custom)
+        .scan_tcp("127.0.0.1".to_owned(), 1, 2, JobConfig::default())
+        .scan_udp("127.0.0.1".to_owned(), 1, 2, job)
+        .report()
+        .unwrap();
+    let config = report.config;
+    assert_eq!(config.thread_count, 2);
+    assert_eq!(config.tcp_timeout, 50);
+    assert_eq!(config.udp_timeout, 60);
+    assert_eq!(config.attemps, 3);
+    assert_eq!(config.connector, "custom");
+    let scans = config
+        .scans
+        .iter()
+        .map(|scan| (scan.protocol.as_str(), scan.timeout, scan.attemps))
+        .collect::<Vec<_>>();
+    assert_eq!(scans, [("tcp", 50, 3), ("udp", 20, 3)]);
+}
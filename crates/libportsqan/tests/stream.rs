@@ -0,0 +1,90 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use libportsqan::ScannerBuilder;
+use server::{
+    Error, JobConfig, Outcome, Output, PortState, Probe, Prober, Protocol, SimulatedNetwork,
+};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+fn network() -> SimulatedNetwork {
+    SimulatedNetwork::default()
+        .port(LOCALHOST, Protocol::Tcp, 2, PortState::Open)
+        .port(LOCALHOST, Protocol::Udp, 1, PortState::Open)
+}
+
+#[test]
+fn stream_yields_only_results_and_ends_at_idle() {
+    let mut stream = ScannerBuilder::default()
+        .prober(network())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 5, JobConfig::default())
+        .scan_udp("127.0.0.1".to_owned(), 1, 3, JobConfig::default())
+        .stream()
+        .unwrap();
+    let outputs = stream.by_ref().collect::<Vec<_>>();
+    // no job, pause or settings events
+    assert_eq!(outputs.len(), 8);
+    assert!(outputs
+        .iter()
+        .all(|output| matches!(output, Output::TcpScan(_) | Output::UdpScan(_))));
+    let open = outputs
+        .iter()
+        .filter_map(|output| match output {
+            Output::TcpScan(r) if r.state == PortState::Open => Some(("tcp", r.address.port())),
+            Output::UdpScan(r) if r.state == PortState::Open => Some(("udp", r.address.port())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(open.len(), 2);
+    assert!(open.contains(&("tcp", 2)) && open.contains(&("udp", 1)));
+    // the scanner is torn down once the scan is over
+    assert!(stream.scanner().is_none());
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn stream_without_scans_is_empty() {
+    let mut stream = ScannerBuilder::default()
+        .prober(network())
+        .stream()
+        .unwrap();
+    assert!(stream.scanner().is_none());
+    assert!(stream.next().is_none());
+}
+
+struct PanicOn(u16);
+
+impl Prober for PanicOn {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        if probe.address.port() == self.0 {
+            panic!("probe of port {}", self.0);
+        }
+        network().tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        network().udp(probe)
+    }
+}
+
+#[test]
+fn stream_yields_errors() {
+    let outputs = ScannerBuilder::default()
+        .prober(PanicOn(2))
+        .scan_tcp("127.0.0.1".to_owned(), 1, 3, JobConfig::default())
+        .run()
+        .unwrap();
+    assert_eq!(outputs.len(), 3);
+    assert!(outputs
+        .iter()
+        .any(|output| matches!(output, Output::Error(Error::ProbeFailed(Protocol::Tcp, ..)))));
+}
+
+#[test]
+fn rejected_settings_fail_the_stream() {
+    let rsl = ScannerBuilder::default()
+        .prober(network())
+        .proxy("ftp://127.0.0.1:21".to_owned())
+        .scan_tcp("127.0.0.1".to_owned(), 1, 3, JobConfig::default())
+        .stream();
+    assert!(matches!(rsl, Err(Error::Proxy(_))));
+}
@@ -1,4 +1,6 @@
-use server::Input;
+use std::net::IpAddr;
+
+use server::{Input, JobConfig};
 
 #[derive(Default)]
 pub struct Parser {
@@ -15,7 +17,7 @@ enum Token {
     Boolean(bool),
     Tcp,
     Udp,
-    EOF,
+    Eof,
 }
 
 #[derive(Debug)]
@@ -40,7 +42,7 @@ impl Parser {
             self.fields.remove(0);
             s
         } else {
-            return Token::EOF;
+            return Token::Eof;
         };
         self.pointer += 1;
         match field.parse::<usize>() {
@@ -92,12 +94,46 @@ impl Parser {
             _ => Err(Error::InvalidParam(self.pointer)),
         }
     }
+    // `none` resets the setting
+    fn parse_optional_string(&mut self) -> Result<Option<String>, Error> {
+        let value = self.parse_string()?;
+        Ok(if value == "none" { None } else { Some(value) })
+    }
+    fn parse_source_ip(&mut self) -> Result<Option<IpAddr>, Error> {
+        match self.parse_optional_string()? {
+            Some(ip) => Ok(Some(
+                ip.parse().map_err(|_| Error::InvalidParam(self.pointer))?,
+            )),
+            None => Ok(None),
+        }
+    }
+    fn parse_source_port(&mut self) -> Result<Option<u16>, Error> {
+        match self.next() {
+            Token::Int(value) if value <= 0xffff => Ok(Some(value as u16)),
+            Token::Int(value) => Err(Error::InvalidPort(value)),
+            Token::String(value) if value == "none" => Ok(None),
+            _ => Err(Error::InvalidParam(self.pointer)),
+        }
+    }
     fn parse_config(&mut self) -> Result<Input, Error> {
         match self.parse_string()?.as_str() {
             "threads" | "thread" | "t" => Ok(Input::Threads(self.parse_number()?)),
             "tries" | "attemps" | "a" => Ok(Input::Attmpts(self.parse_number()?)),
             "tcp-timeout" | "timeout" | "tto" => Ok(Input::TcpTimeout(self.parse_number()?)),
             "udp-timeout" | "uto" => Ok(Input::UdpTimeout(self.parse_number()?)),
+            "host-limit" | "hl" => Ok(Input::HostLimit(self.parse_number()?)),
+            "subnet-limit" | "sl" => Ok(Input::SubnetLimit(self.parse_number()?)),
+            "resolve-all" | "ra" => Ok(Input::ResolveAll(self.parse_boolean()?)),
+            "dns-ttl" => Ok(Input::DnsTtl(self.parse_number()?)),
+            "reverse-dns" | "rdns" => Ok(Input::ReverseDns(self.parse_boolean()?)),
+            "rdns-timeout" => Ok(Input::RdnsTimeout(self.parse_number()?)),
+            "rdns-concurrency" => Ok(Input::RdnsConcurrency(self.parse_number()?)),
+            "source-ip" | "sip" => Ok(Input::SourceIp(self.parse_source_ip()?)),
+            "source-port" | "sport" => Ok(Input::SourcePort(self.parse_source_port()?)),
+            "interface" | "iface" => Ok(Input::Interface(self.parse_optional_string()?)),
+            "proxy" => Ok(Input::Proxy(self.parse_optional_string()?)),
+            "netns" => Ok(Input::Netns(self.parse_optional_string()?)),
+            "audit-log" | "audit" => Ok(Input::AuditLog(self.parse_string()?)),
             "autostop" => {
                 self.state.autostop = self.parse_boolean()?;
                 Ok(Input::NOP)
@@ -106,9 +142,25 @@ impl Parser {
         }
     }
 
+    fn parse_job_config(&mut self) -> Result<JobConfig, Error> {
+        let mut config = JobConfig::default();
+        loop {
+            match self.next() {
+                Token::Eof => break,
+                Token::String(name) => match name.as_str() {
+                    "timeout" | "t" => config = config.timeout(self.parse_number()?),
+                    "tries" | "attemps" | "a" => config = config.attemps(self.parse_number()?),
+                    "priority" | "p" => config = config.priority(self.parse_number()?),
+                    _ => return Err(Error::InvalidParam(self.pointer)),
+                },
+                _ => return Err(Error::InvalidParam(self.pointer)),
+            }
+        }
+        Ok(config)
+    }
+
     fn parse_scan(&mut self) -> Result<Input, Error> {
         let host = if let Token::String(name) = self.peek() {
-            let name = name;
             self.next();
             name
         } else {
@@ -125,6 +177,7 @@ impl Parser {
         };
         let from = self.parse_number()?;
         let to = if let Token::Int(num) = self.peek() {
+            self.next();
             num
         } else {
             from
@@ -135,10 +188,11 @@ impl Parser {
         if to > 0xffff {
             return Err(Error::InvalidPort(to));
         }
+        let config = self.parse_job_config()?;
         Ok(if is_tcp {
-            Input::TcpRange(host, from as u16, to as u16)
+            Input::TcpRange(host, from as u16, to as u16, config)
         } else {
-            Input::UdpRange(host, from as u16, to as u16)
+            Input::UdpRange(host, from as u16, to as u16, config)
         })
     }
     pub fn parse_fields(&mut self) -> Result<Input, Error> {
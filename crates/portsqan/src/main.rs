@@ -1,10 +1,13 @@
 mod repl;
+mod report;
 
-use std::process::exit;
+use std::{net::IpAddr, process::exit};
 
-use clap::{command, Parser};
+use clap::Parser;
 use libportsqan::ScannerBuilder;
 use repl::run_repl;
+use report::print_report;
+use server::JobConfig;
 
 #[derive(Parser)]
 #[command(name = "Portsqan")]
@@ -42,6 +45,52 @@ struct CliArgs {
 
     #[clap(long)]
     stale: Option<bool>,
+
+    #[clap(long)]
+    host_limit: Option<usize>,
+
+    #[clap(long)]
+    subnet_limit: Option<usize>,
+
+    #[clap(long)]
+    resolve_all: Option<bool>,
+
+    /// Seconds a resolved name is reused. The system resolver does not
+    /// report record TTLs, so this fixed time applies to every name
+    #[clap(long)]
+    dns_ttl: Option<usize>,
+
+    #[clap(long)]
+    reverse_dns: Option<bool>,
+
+    #[clap(long)]
+    rdns_timeout: Option<usize>,
+
+    #[clap(long)]
+    rdns_concurrency: Option<usize>,
+
+    #[clap(long)]
+    source_ip: Option<IpAddr>,
+
+    #[clap(long)]
+    source_port: Option<u16>,
+
+    #[clap(long)]
+    interface: Option<String>,
+
+    #[clap(long)]
+    netns: Option<String>,
+
+    #[clap(long)]
+    proxy: Option<String>,
+
+    #[clap(long)]
+    audit_log: Option<String>,
+
+    /// Scan without the prompt and print the results grouped by host,
+    /// as `text` or `json`
+    #[clap(long, value_parser = ["text", "json"])]
+    report: Option<String>,
 }
 
 fn zero_port_check(port: u16) {
@@ -84,6 +133,45 @@ fn main() {
     if let Some(value) = args.stale {
         builder = builder.stale(value);
     }
+    if let Some(value) = args.host_limit {
+        builder = builder.host_limit(value);
+    }
+    if let Some(value) = args.subnet_limit {
+        builder = builder.subnet_limit(value);
+    }
+    if let Some(value) = args.resolve_all {
+        builder = builder.resolve_all(value);
+    }
+    if let Some(value) = args.dns_ttl {
+        builder = builder.dns_ttl(value);
+    }
+    if let Some(value) = args.reverse_dns {
+        builder = builder.reverse_dns(value);
+    }
+    if let Some(value) = args.rdns_timeout {
+        builder = builder.rdns_timeout(value);
+    }
+    if let Some(value) = args.rdns_concurrency {
+        builder = builder.rdns_concurrency(value);
+    }
+    if let Some(value) = args.source_ip {
+        builder = builder.source_ip(value);
+    }
+    if let Some(value) = args.source_port {
+        builder = builder.source_port(value);
+    }
+    if let Some(value) = args.interface {
+        builder = builder.interface(value);
+    }
+    if let Some(value) = args.netns {
+        builder = builder.netns(value);
+    }
+    if let Some(value) = args.proxy {
+        builder = builder.proxy(value);
+    }
+    if let Some(value) = args.audit_log {
+        builder = builder.audit_log(value);
+    }
 
     let is_tcp = args
         .protocol
@@ -110,13 +198,16 @@ fn main() {
     for r in ranges.drain(..) {
         let (from, to) = r;
         if is_tcp {
-            builder = builder.scan_tcp(args.host.clone(), from, to);
+            builder = builder.scan_tcp(args.host.clone(), from, to, JobConfig::default());
         } else {
-            builder = builder.scan_udp(args.host.clone(), from, to);
+            builder = builder.scan_udp(args.host.clone(), from, to, JobConfig::default());
         }
     }
 
-    run_repl(builder, args.host.clone());
+    match args.report {
+        Some(format) => print_report(builder, &format),
+        None => run_repl(builder, args.host.clone()),
+    }
 }
 
 fn range(f: u16, t: u16, xf: u16, xt: u16) -> Vec<(u16, u16)> {
@@ -6,74 +6,68 @@ use std::{
 use libportsqan::ScannerBuilder;
 use parser::{Parser, ReplConfig};
 use rustyline::{error::ReadlineError, DefaultEditor, ExternalPrinter};
-use server::{Input, Output, Scanner};
-
-enum TerminalState {
-    Log,
-    Store,
-}
+use server::{Error, Input, Output, Reply, Scanner, DEFAULT_SUBSCRIBER};
 
 struct Terminal<P: ExternalPrinter> {
-    buffered_output: Vec<Output>,
-    state: TerminalState,
     printer: P,
 }
 
 impl<P: ExternalPrinter> Terminal<P> {
     fn new(printer: P) -> Self {
-        Self {
-            buffered_output: vec![],
-            state: TerminalState::Log,
-            printer,
-        }
-    }
-
-    fn clear_scan_results(&mut self) {
-        self.buffered_output = self
-            .buffered_output
-            .drain(..)
-            .filter(|s| match s {
-                Output::TcpScan(_, _, _) | Output::UdpScan(_, _, _) => false,
-                _ => true,
-            })
-            .collect::<Vec<_>>()
-    }
-
-    fn flush(&mut self) {
-        while self.buffered_output.len() > 0 {
-            let o = self.buffered_output.remove(0);
-            self.print(o);
-        }
+        Self { printer }
     }
 
     fn print(&mut self, output: Output) {
-        let _ = self.printer.print(format!("| {:?}\n", output));
+        let _ = self.printer.print(format!("| {}\n", output));
     }
-}
 
-fn stop<P: ExternalPrinter>(scanner: &Scanner, terminal: Arc<Mutex<Terminal<P>>>, silent: bool) {
-    if let Ok(mut terminal) = terminal.lock() {
-        terminal.state = TerminalState::Store;
-        if let Some(output) = scanner.command(Input::Stop) {
-            if !silent {
-                terminal.print(output);
+    fn print_reply(&mut self, reply: Result<Reply, Error>) {
+        match reply {
+            Ok(reply) => {
+                let _ = self.printer.print(format!("| {:?}\n", reply));
             }
+            Err(err) => self.print(Output::Error(err)),
         }
     }
 }
 
-fn resume<P: ExternalPrinter>(scanner: &Scanner, terminal: Arc<Mutex<Terminal<P>>>, silent: bool) {
-    if let Ok(mut terminal) = terminal.lock() {
-        terminal.state = TerminalState::Log;
-        terminal.flush();
-        if let Some(output) = scanner.command(Input::Cont) {
-            if !silent {
-                terminal.print(output);
-            }
+// the terminal's subscription is held while paused, so that results
+// don't get in the way of typing. commands are sent without the
+// terminal locked, the master may be printing through it
+fn command<P: ExternalPrinter>(
+    scanner: &Scanner,
+    terminal: &Arc<Mutex<Terminal<P>>>,
+    input: Input,
+    silent: bool,
+) {
+    let reply = scanner.command(input);
+    if !silent {
+        if let Ok(mut terminal) = terminal.lock() {
+            terminal.print_reply(reply);
         }
     }
 }
 
+fn stop<P: ExternalPrinter>(scanner: &Scanner, terminal: &Arc<Mutex<Terminal<P>>>, silent: bool) {
+    command(
+        scanner,
+        terminal,
+        Input::Hold(DEFAULT_SUBSCRIBER, true),
+        true,
+    );
+    command(scanner, terminal, Input::Stop, silent);
+}
+
+fn resume<P: ExternalPrinter>(scanner: &Scanner, terminal: &Arc<Mutex<Terminal<P>>>, silent: bool) {
+    command(
+        scanner,
+        terminal,
+        Input::Hold(DEFAULT_SUBSCRIBER, false),
+        true,
+    );
+    command(scanner, terminal, Input::Cont, silent);
+}
+
 pub fn run_repl(config: ScannerBuilder, host: String) {
     let (int_tx, int_rx) = crossbeam::channel::bounded(1);
     let handler = move || {
@@ -86,14 +80,16 @@ pub fn run_repl(config: ScannerBuilder, host: String) {
         rl.create_external_printer().unwrap(),
     )));
     let tclone = terminal.clone();
-    let scanner = config.build(move |output| {
-        if let Ok(mut terminal) = tclone.lock() {
-            match terminal.state {
-                TerminalState::Log => terminal.print(output),
-                TerminalState::Store => terminal.buffered_output.push(output),
+    let scanner = config
+        .build(move |output| {
+            if let Ok(mut terminal) = tclone.lock() {
+                terminal.print(output);
             }
-        }
-    });
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("ERROR: {}", err);
+            exit(1)
+        });
     let mut state = ReplConfig {
         host: Some(host),
         autostop: true,
@@ -103,37 +99,23 @@ pub fn run_repl(config: ScannerBuilder, host: String) {
     loop {
         int_rx.recv().unwrap();
         if state.autostop {
-            stop(&scanner, terminal.clone(), true);
+            stop(&scanner, &terminal, true);
         }
         let exit = loop {
             let prompt = format!("{}> ", state.host.clone().unwrap_or("".to_owned()));
             match rl.readline(prompt.as_str()) {
                 Ok(line) => {
                     let _ = rl.add_history_entry(&line);
-                    if line.trim().len() > 0 {
+                    if !line.trim().is_empty() {
                         let (rsl, new_state) = parser.parse(state, line);
                         state = new_state;
                         match rsl {
                             Ok(input) => {
                                 match input {
-                                    Input::Stop => stop(&scanner, terminal.clone(), false),
-                                    Input::Cont => resume(&scanner, terminal.clone(), false),
+                                    Input::Stop => stop(&scanner, &terminal, false),
+                                    Input::Cont => resume(&scanner, &terminal, false),
                                     Input::End => break true,
-                                    Input::Cancel => {
-                                        if let Ok(mut terminal) = terminal.lock() {
-                                            terminal.clear_scan_results();
-                                            if let Some(output) = scanner.command(input) {
-                                                terminal.print(output)
-                                            }
-                                        }
-                                    }
-                                    _ => {
-                                        if let Ok(mut terminal) = terminal.lock() {
-                                            if let Some(output) = scanner.command(input) {
-                                                terminal.print(output)
-                                            }
-                                        }
-                                    }
+                                    _ => command(&scanner, &terminal, input, false),
                                 };
                             }
                             Err(err) => eprintln!("Error: {:?}", err),
@@ -151,8 +133,8 @@ pub fn run_repl(config: ScannerBuilder, host: String) {
         if exit {
             break;
         }
-        resume(&scanner, terminal.clone(), true)
+        resume(&scanner, &terminal, true)
     }
-    scanner.command(Input::End);
-    scanner.join();
+    let _ = scanner.command(Input::End);
+    let _ = scanner.join();
 }
@@ -0,0 +1,71 @@
+use std::{collections::BTreeSet, process::exit};
+
+use libportsqan::{ScanReport, ScannerBuilder};
+
+// `1-21, 23, 25-79`
+fn ranges(ports: &BTreeSet<u16>) -> String {
+    let mut runs: Vec<(u16, u16)> = vec![];
+    for &port in ports {
+        match runs.last_mut() {
+            Some((_, to)) if *to as u32 + 1 == port as u32 => *to = port,
+            _ => runs.push((port, port)),
+        }
+    }
+    runs.iter()
+        .map(|&(from, to)| {
+            if from == to {
+                from.to_string()
+            } else {
+                format!("{}-{}", from, to)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn text(report: &ScanReport) -> String {
+    let mut text = format!("{} probes in {} ms\n", report.probes, report.duration);
+    for host in &report.hosts {
+        text += &host.host;
+        if host.address.to_string() != host.host && !host.address.is_unspecified() {
+            text += &format!(" ({})", host.address);
+        }
+        if let Some(name) = &host.name {
+            text += &format!(" {}", name);
+        }
+        text += "\n";
+        for (protocol, sets) in [("tcp", &host.tcp), ("udp", &host.udp)] {
+            for (state, ports) in [
+                ("open", &sets.open),
+                ("closed", &sets.closed),
+                ("filtered", &sets.filtered),
+            ] {
+                if !ports.is_empty() {
+                    text += &format!("  {} {:<8} {}\n", protocol, state, ranges(ports));
+                }
+            }
+        }
+    }
+    for err in &report.errors {
+        text += &format!("ERROR: {}\n", err);
+    }
+    text
+}
+
+// runs the scan to its end without the repl
+pub fn print_report(builder: ScannerBuilder, format: &str) {
+    let report = builder.report().unwrap_or_else(|err| {
+        eprintln!("ERROR: {}", err);
+        exit(1)
+    });
+    match format {
+        "json" => match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("ERROR: {}", err);
+                exit(1)
+            }
+        },
+        _ => print!("{}", text(&report)),
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{AddressRange, JobId, Protocol, ScannerConfig};
+
+// append-only record of every job the scanner has been given,
+// one `key=value` line per event
+
+// user given values are quoted and escaped, a name can not add
+// fields or lines of its own
+fn quote(value: &str) -> String {
+    format!("{:?}", value)
+}
+
+fn or_none<T: ToString>(value: Option<T>) -> String {
+    value.map_or("none".to_owned(), |value| value.to_string())
+}
+
+pub struct AuditLog {
+    file: Option<File>,
+    uid: u32, // the environment's name is up to the caller, the uid is not
+    user: String,
+}
+
+impl AuditLog {
+    pub fn new() -> AuditLog {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .unwrap_or("unknown".to_owned());
+        AuditLog {
+            file: None,
+            uid: unsafe { libc::getuid() },
+            user,
+        }
+    }
+    pub fn open(&mut self, path: &str) -> io::Result<()> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        self.file = Some(file);
+        Ok(())
+    }
+    fn write(&mut self, event: &str, job: JobId, details: String) -> io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            writeln!(
+                file,
+                "time={}.{:03} uid={} user={} pid={} event={} job={}{}",
+                time.as_secs(),
+                time.subsec_millis(),
+                self.uid,
+                quote(&self.user),
+                std::process::id(),
+                event,
+                job,
+                details
+            )?;
+        }
+        Ok(())
+    }
+    pub fn queued(
+        &mut self,
+        job: JobId,
+        ranges: &[AddressRange],
+        config: &ScannerConfig,
+    ) -> io::Result<()> {
+        let range = match ranges.first() {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+        let (protocol, timeout) = match range.protocol {
+            Protocol::Tcp => ("tcp", range.config.tcp_timeout(config)),
+            Protocol::Udp => ("udp", range.config.udp_timeout(config)),
+        };
+        let addresses = ranges
+            .iter()
+            .map(|r| r.ip.ip().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        // everything that decides what the scan touches and from where
+        let source = &config.source;
+        let details = format!(
+            " host={} addresses={} protocol={} ports={}-{} priority={} threads={} attemps={} timeout={} stale={} \
+             resolve_all={} host_limit={} subnet_limit={} source_ip={} source_port={} interface={} netns={} proxy={}",
+            quote(&range.host),
+            addresses,
+            protocol,
+            range.from,
+            range.to,
+            range.config.priority,
+            config.thread_count,
+            range.config.attemps_or(config),
+            timeout,
+            config.stale,
+            config.resolve_all,
+            config.host_limit,
+            config.subnet_limit,
+            or_none(source.ip),
+            or_none(source.port),
+            or_none(source.interface.as_deref().map(quote)),
+            or_none(config.netns.as_ref().map(|ns| quote(ns.name()))),
+            quote(&config.connector.describe()),
+        );
+        self.write("queued", job, details)
+    }
+    pub fn completed(&mut self, job: JobId) -> io::Result<()> {
+        self.write("completed", job, String::new())
+    }
+    pub fn cancelled(&mut self, job: JobId) -> io::Result<()> {
+        self.write("cancelled", job, String::new())
+    }
+}
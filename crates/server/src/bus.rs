@@ -0,0 +1,103 @@
+use std::fmt::Debug;
+
+use crate::Output;
+
+pub type SubscriberId = usize;
+
+// the `output` given to `Scanner::new`
+pub const DEFAULT_SUBSCRIBER: SubscriberId = 1;
+
+// a listener attached at runtime with `Input::Subscribe`, it gets every
+// output its filter lets through
+pub struct Subscriber {
+    filter: Box<dyn Fn(&Output) -> bool + Send>,
+    callback: Box<dyn Fn(Output) + Send>,
+}
+
+impl Subscriber {
+    pub fn new<C: Fn(Output) + Send + 'static>(callback: C) -> Subscriber {
+        Subscriber {
+            filter: Box::new(|_| true),
+            callback: Box::new(callback),
+        }
+    }
+    pub fn filter<F: Fn(&Output) -> bool + Send + 'static>(self, filter: F) -> Self {
+        let mut s = self;
+        s.filter = Box::new(filter);
+        s
+    }
+}
+
+impl Debug for Subscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Subscriber")
+    }
+}
+
+struct Subscription {
+    id: SubscriberId,
+    subscriber: Subscriber,
+    held: Option<Vec<Output>>, // kept back until released, in order
+}
+
+// fans the master's outputs out to the subscribers
+#[derive(Default)]
+pub struct Bus {
+    subscriptions: Vec<Subscription>,
+    id_counter: SubscriberId,
+}
+
+impl Bus {
+    pub fn subscribe(&mut self, subscriber: Subscriber) -> SubscriberId {
+        self.id_counter += 1;
+        self.subscriptions.push(Subscription {
+            id: self.id_counter,
+            subscriber,
+            held: None,
+        });
+        self.id_counter
+    }
+    pub fn unsubscribe(&mut self, id: SubscriberId) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+        self.subscriptions.len() != count
+    }
+    // a held subscriber gets nothing until released, then everything it
+    // missed at once
+    pub fn hold(&mut self, id: SubscriberId, hold: bool) -> bool {
+        let subscription = match self.subscriptions.iter_mut().find(|s| s.id == id) {
+            Some(subscription) => subscription,
+            None => return false,
+        };
+        if hold {
+            subscription.held.get_or_insert_with(Vec::new);
+        } else if let Some(held) = subscription.held.take() {
+            for output in held {
+                (subscription.subscriber.callback)(output);
+            }
+        }
+        true
+    }
+    // results held back from before a cancel are as stale as the ones
+    // still in flight
+    pub fn discard_results(&mut self) {
+        for held in self
+            .subscriptions
+            .iter_mut()
+            .filter_map(|s| s.held.as_mut())
+        {
+            held.retain(|o| !matches!(o, Output::TcpScan(_) | Output::UdpScan(_)));
+        }
+    }
+    pub fn publish(&mut self, output: Output) {
+        for subscription in self.subscriptions.iter_mut() {
+            if !(subscription.subscriber.filter)(&output) {
+                continue;
+            }
+            match subscription.held.as_mut() {
+                Some(held) => held.push(output.clone()),
+                None => (subscription.subscriber.callback)(output.clone()),
+            }
+        }
+    }
+}
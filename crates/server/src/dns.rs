@@ -0,0 +1,70 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{net, Error, Protocol, ScannerConfig};
+
+// the system resolver does not expose record TTLs,
+// so entries are kept for the configured `dns_ttl`.
+// shared by every `Scanner` clone, lookups run on the caller's thread
+#[derive(Default)]
+pub struct DnsCache {
+    entries: Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>,
+}
+
+impl DnsCache {
+    // the lock is not held during the lookup, one slow name does not
+    // hold up the others
+    pub fn resolve(&self, host: &str, ttl: Duration) -> Option<Vec<SocketAddr>> {
+        if let Some((time, addresses)) = self.entries.lock().ok()?.get(host) {
+            if time.elapsed() < ttl {
+                return Some(addresses.clone());
+            }
+        }
+        let addresses = net::lookup(host)?;
+        self.entries
+            .lock()
+            .ok()?
+            .insert(host.to_owned(), (Instant::now(), addresses.clone()));
+        Some(addresses)
+    }
+    // the addresses a job scans, under the settings at the time it is queued
+    pub fn job_addresses(
+        &self,
+        host: &str,
+        protocol: Protocol,
+        config: &Mutex<ScannerConfig>,
+    ) -> Result<Vec<SocketAddr>, Error> {
+        let host = net::strip_brackets(host);
+        let (resolve_all, dns_ttl, remote_dns, source_ip) = {
+            let config = config.lock().map_err(|_| Error::MasterPanic)?;
+            let tcp = protocol == Protocol::Tcp;
+            let remote_dns = tcp && config.connector.remote_dns();
+            // only a socket going to the target is bound to the source ip
+            let direct = !tcp || config.connector.direct();
+            let source_ip = config.source.ip.filter(|_| direct);
+            (config.resolve_all, config.dns_ttl, remote_dns, source_ip)
+        };
+        let mut addresses = if remote_dns && host.parse::<IpAddr>().is_err() {
+            // handed to the proxy by name, see `format_address`
+            vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)]
+        } else {
+            self.resolve(host, Duration::from_secs(dns_ttl as u64))
+                .ok_or(Error::Resolve(host.to_owned()))?
+        };
+        // would fail to bind, not worth a probe
+        if let Some(ip) = source_ip {
+            addresses.retain(|address| address.is_ipv4() == ip.is_ipv4());
+            if addresses.is_empty() {
+                return Err(Error::Family(host.to_owned()));
+            }
+        }
+        if !resolve_all {
+            addresses.truncate(1);
+        }
+        Ok(addresses)
+    }
+}
@@ -1,45 +1,57 @@
+mod audit;
+mod bus;
+mod dns;
+mod limits;
 mod net;
+mod netns;
+mod probe;
+mod proxy;
+mod queue;
+mod rdns;
+mod sim;
 
 use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
     sync::{Arc, Mutex, MutexGuard},
     thread::JoinHandle,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
     vec,
 };
 
+use audit::AuditLog;
+use bus::Bus;
+pub use bus::{Subscriber, SubscriberId, DEFAULT_SUBSCRIBER};
 use crossbeam::{
     channel::{Receiver, Sender},
     select,
 };
+use dns::DnsCache;
+pub use net::{connector_failure, socket_failure, Connector, Direct, Source};
+use netns::Namespace;
+pub use probe::{Cancellation, Failure, Outcome, Probe, Prober, SocketProber};
+use proxy::Proxy;
+use queue::ScanQueue;
+use rdns::ReverseDns;
+pub use sim::{SimulatedNetwork, VirtualClock};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Protocol {
     Tcp,
     Udp,
 }
 
 pub struct AddressRange {
-    host: String,
+    job: JobId,
+    host: Host,
+    ip: SocketAddr,
     protocol: Protocol,
     from: u16,
     to: u16,
+    config: JobConfig,
 }
 
-impl AddressRange {
-    fn len(&self) -> usize {
-        (self.to - self.from + 1) as usize
-    }
-    fn nth(&self, index: usize) -> u16 {
-        self.from + index as u16
-    }
-}
-
-pub struct Port {
-    protocol: Protocol,
-    number: u16,
-}
-
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum PortState {
     Open,
     Closed,
@@ -52,6 +64,16 @@ pub struct ScannerConfig {
     tcp_timeout: usize, // miliseconds
     udp_timeout: usize, // miliseconds
     attemps: usize,
+    host_limit: usize,   // in-flight probes per host, 0 for no limit
+    subnet_limit: usize, // in-flight probes per IPv4 /24, 0 for no limit
+    resolve_all: bool,   // scan every address a name resolves to, not only the first
+    dns_ttl: usize,      // seconds
+    reverse_dns: bool,
+    rdns_timeout: usize, // miliseconds
+    rdns_concurrency: usize,
+    source: net::Source,
+    netns: Option<Namespace>,
+    connector: Arc<dyn Connector>, // TCP only
 }
 
 impl Default for ScannerConfig {
@@ -62,6 +84,16 @@ impl Default for ScannerConfig {
             stale: true,
             tcp_timeout: 500,
             udp_timeout: 500,
+            host_limit: 0,
+            subnet_limit: 0,
+            resolve_all: false,
+            dns_ttl: 60,
+            reverse_dns: false,
+            rdns_timeout: 1000,
+            rdns_concurrency: 4,
+            source: net::Source::default(),
+            netns: None,
+            connector: Arc::new(Direct),
         }
     }
 }
@@ -74,60 +106,105 @@ impl ScannerConfig {
     }
 }
 
-#[derive(Default)]
-struct ScanQueue {
-    address_index: usize,
-    ranges: Vec<AddressRange>,
+// the settings a scanner runs with, defaults included, see `Scanner::settings`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Settings {
+    pub thread_count: usize,
+    pub stale: bool,
+    pub tcp_timeout: usize, // miliseconds
+    pub udp_timeout: usize, // miliseconds
+    pub attemps: usize,
+    pub host_limit: usize,
+    pub subnet_limit: usize,
+    pub resolve_all: bool,
+    pub dns_ttl: usize, // seconds
+    pub reverse_dns: bool,
+    pub rdns_timeout: usize, // miliseconds
+    pub rdns_concurrency: usize,
+    pub source_ip: Option<IpAddr>, // `None` when left to the kernel
+    pub source_port: Option<u16>,
+    pub interface: Option<String>,
+    pub netns: Option<String>,
+    pub connector: String, // see `Connector::describe`
 }
 
-impl ScanQueue {
-    fn new() -> ScanQueue {
-        ScanQueue {
-            address_index: 0,
-            ranges: vec![],
-        }
+// per-job settings, the optional ones take precedence over the global `ScannerConfig`
+#[derive(Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct JobConfig {
+    pub timeout: Option<usize>, // miliseconds
+    pub attemps: Option<usize>,
+    pub priority: usize,
+}
+
+impl JobConfig {
+    pub fn timeout(self, timeout: usize) -> JobConfig {
+        let mut s = self;
+        s.timeout = Some(timeout);
+        s
     }
-    fn pop(&mut self) -> Option<Address> {
-        if let Some(address_range) = self.ranges.first() {
-            if address_range.len() > self.address_index {
-                let number = address_range.nth(self.address_index);
-                self.address_index += 1;
-                let address = (
-                    address_range.host.clone(),
-                    Port {
-                        protocol: address_range.protocol,
-                        number,
-                    },
-                );
-                Some(address)
-            } else {
-                self.ranges.remove(0);
-                self.address_index = 0;
-                self.pop()
-            }
-        } else {
-            None
-        }
+    pub fn attemps(self, attemps: usize) -> JobConfig {
+        let mut s = self;
+        s.attemps = Some(attemps);
+        s
+    }
+    pub fn priority(self, priority: usize) -> JobConfig {
+        let mut s = self;
+        s.priority = priority;
+        s
+    }
+    fn tcp_timeout(&self, config: &ScannerConfig) -> usize {
+        self.timeout.unwrap_or(config.tcp_timeout)
     }
-    fn push(&mut self, address_range: AddressRange) {
-        self.ranges.push(address_range)
+    fn udp_timeout(&self, config: &ScannerConfig) -> usize {
+        self.timeout.unwrap_or(config.udp_timeout)
     }
-    fn len(&self) -> usize {
-        self.ranges.len()
+    fn attemps_or(&self, config: &ScannerConfig) -> usize {
+        self.attemps.unwrap_or(config.attemps)
     }
+}
 
-    fn clear(&mut self) {
-        self.ranges.clear();
-        self.address_index = 0;
+// shared by every probe of a job
+type Host = Arc<str>;
+
+// none for the unspecified address a proxy resolves by name
+fn subnet(ip: IpAddr) -> Option<[u8; 3]> {
+    match ip {
+        IpAddr::V4(ip) if !ip.is_unspecified() => {
+            let octets = ip.octets();
+            Some([octets[0], octets[1], octets[2]])
+        }
+        _ => None,
     }
 }
 
-type Host = String;
+// the name as given by the user, the concrete address it resolved to
+type Address = (Host, SocketAddr, Protocol);
+
+pub type JobId = usize;
+
+struct Job {
+    remaining: usize,
+    started: bool,
+    summary: JobSummary,
+}
 
-type Address = (Host, Port);
+// a job as queued, with the tally of its results so far
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct JobSummary {
+    pub id: JobId,
+    pub host: String,
+    pub protocol: Protocol,
+    pub from: u16,
+    pub to: u16,
+    pub probes: usize,
+    pub open: usize,
+    pub closed: usize,
+    pub unreachable: usize,
+    pub failed: usize, // probes that panicked
+}
 
 enum Instruction {
-    Scan(Address),
+    Scan(JobId, Address, JobConfig, Cancellation),
     Term,
 }
 
@@ -139,7 +216,11 @@ struct WorkerMessage {
 }
 
 enum Message {
-    Scan(Host, Port, PortState),
+    Scan(JobId, Address, Outcome, SystemTime),
+    Cancelled(JobId),
+    Netns(JobId, std::io::ErrorKind), // the probe could not be started
+    Failed(JobId, Address, Failure),  // the probe could not be made
+    Panic(Option<(JobId, Address)>),
 }
 
 struct Worker {
@@ -147,12 +228,18 @@ struct Worker {
     work_rx: Receiver<Instruction>,
     message_tx: Sender<WorkerMessage>,
     config: Arc<Mutex<ScannerConfig>>,
+    prober: Arc<dyn Prober>,
+    probe: Option<(JobId, Address)>,
+    netns: Option<Namespace>, // the one entered, `None` for the initial
+    initial_netns: Option<Namespace>, // to return to once the setting is cleared
 }
 
 struct WorkerHandle {
     stale: bool,
     id: WorkerId,
     state: WorkerState,
+    host: Option<(Host, IpAddr)>, // being probed
+    cancel: Cancellation,         // of the probe in flight
     work_tx: Sender<Instruction>,
     join_handle: Option<JoinHandle<()>>,
 }
@@ -164,17 +251,16 @@ impl WorkerHandle {
     fn is_term(&self) -> bool {
         self.state == WorkerState::Term
     }
-    fn join(&mut self) {
+    fn join(&mut self) -> Result<(), Error> {
         if let Some(h) = self.join_handle.take() {
-            h.join()
-                .expect(format!("FATAL: Worker #{} has paniced!", self.id).as_str());
+            h.join().map_err(|_| Error::WorkerPanic(self.id))?;
         }
+        Ok(())
     }
-    fn send_instruction(&self, instruction: Instruction) {
-        self.work_tx.send(instruction).expect(
-            "FATAL: Scanner failed to send instruction. \
-        The thread has been probably terminated too early, or either the instruction is late.",
-        )
+    fn send_instruction(&self, instruction: Instruction) -> Result<(), Error> {
+        self.work_tx
+            .send(instruction)
+            .map_err(|_| Error::WorkerPanic(self.id))
     }
 }
 
@@ -182,6 +268,7 @@ impl WorkerHandle {
 enum WorkerState {
     Term,
     Working,
+    Retiring, // out of the pool, ends once its probe is done
     Idle,
 }
 #[derive(PartialEq, Eq)]
@@ -204,42 +291,93 @@ impl Worker {
 The channel has been probably closed by the scanner too early.",
             );
     }
-    fn config(&self) -> MutexGuard<ScannerConfig> {
+    fn config(&self) -> MutexGuard<'_, ScannerConfig> {
         self.config.lock().unwrap()
     }
-    fn tcp(&self, host: String, number: u16) -> Option<bool> {
+    fn probe(
+        &self,
+        host: &str,
+        address: SocketAddr,
+        protocol: Protocol,
+        job_config: JobConfig,
+        cancel: &Cancellation,
+    ) -> Outcome {
         let config = self.config();
-        let attemps = config.attemps;
-        let timeout = config.tcp_timeout;
+        let attemps = job_config.attemps_or(&config);
+        let timeout = match protocol {
+            Protocol::Tcp => job_config.tcp_timeout(&config),
+            Protocol::Udp => job_config.udp_timeout(&config),
+        };
+        let source = config.source.clone();
+        let connector = config.connector.clone();
         drop(config);
-        net::scan_tcp(host, number, Duration::from_millis(timeout as u64), attemps)
+        let probe = Probe {
+            host,
+            address,
+            timeout: Duration::from_millis(timeout as u64),
+            attemps,
+            source: &source,
+            connector: connector.as_ref(),
+            cancel,
+        };
+        match protocol {
+            Protocol::Tcp => self.prober.tcp(&probe),
+            Protocol::Udp => self.prober.udp(&probe),
+        }
     }
-    fn udp(&self, host: String, number: u16) -> Option<bool> {
-        let config = self.config();
-        let attemps = config.attemps;
-        let timeout = config.udp_timeout;
-        drop(config);
-        net::scan_udp(host, number, Duration::from_millis(timeout as u64), attemps)
-    }
-    fn run(&self) {
-        loop {
-            match self.work_rx.recv().unwrap_or(Instruction::Term) {
-                Instruction::Scan((host, port)) => {
-                    let scan = match port.protocol {
-                        Protocol::Tcp => self.tcp(host.clone(), port.number),
-                        Protocol::Udp => self.udp(host.clone(), port.number),
-                    };
-                    let scan = match scan {
-                        Some(true) => PortState::Open,
-                        Some(false) => PortState::Closed,
-                        None => PortState::Unreachable,
-                    };
-                    self.send_message(Message::Scan(host, port, scan));
-                }
-                Instruction::Term => {
-                    break;
-                }
+    fn switch_netns(&mut self) -> std::io::Result<()> {
+        let wanted = self.config().netns.clone();
+        if wanted == self.netns {
+            return Ok(());
+        }
+        wanted
+            .as_ref()
+            .or(self.initial_netns.as_ref())
+            .ok_or(std::io::ErrorKind::NotFound)?
+            .enter()?;
+        self.netns = wanted;
+        Ok(())
+    }
+    fn run(&mut self) {
+        while let Instruction::Scan(job, address, job_config, cancel) =
+            self.work_rx.recv().unwrap_or(Instruction::Term)
+        {
+            // kept around so the master can be told which address was lost
+            // in case the probe panics
+            self.probe = Some((job, address.clone()));
+            // probing from another namespace than the one asked for
+            // would report the wrong network
+            if let Err(err) = self.switch_netns() {
+                self.probe = None;
+                self.send_message(Message::Netns(job, err.kind()));
+                continue;
+            }
+            let (host, ip, protocol) = &address;
+            let outcome = self.probe(host, *ip, *protocol, job_config, &cancel);
+            let time = SystemTime::now();
+            if let Some(failure) = outcome.failure {
+                self.probe = None;
+                self.send_message(Message::Failed(job, address, failure));
+                continue;
+            }
+            self.probe = None;
+            // an aborted probe says nothing about the port
+            if cancel.is_cancelled() {
+                self.send_message(Message::Cancelled(job));
+                continue;
             }
+            self.send_message(Message::Scan(job, address, outcome, time));
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let _ = self.message_tx.send(WorkerMessage {
+                worker_id: self.id,
+                content: Message::Panic(self.probe.take()),
+            });
         }
     }
 }
@@ -249,8 +387,9 @@ pub enum Input {
     Stop,
     Cont,
     End,
-    TcpRange(String, u16, u16),
-    UdpRange(String, u16, u16),
+    Shutdown(usize), // miliseconds the probes in flight have to finish
+    TcpRange(String, u16, u16, JobConfig),
+    UdpRange(String, u16, u16, JobConfig),
     Threads(usize),
     Stale(bool),
     Cancel,
@@ -259,53 +398,283 @@ pub enum Input {
     Attmpts(usize),
     TcpTimeout(usize),
     UdpTimeout(usize),
+    AuditLog(String),
+    HostLimit(usize),
+    SubnetLimit(usize),
+    ResolveAll(bool),
+    DnsTtl(usize),
+    ReverseDns(bool),
+    RdnsTimeout(usize),
+    RdnsConcurrency(usize),
+    SourceIp(Option<IpAddr>),
+    SourcePort(Option<u16>),
+    Interface(Option<String>),
+    Netns(Option<String>),
+    Proxy(Option<String>),
+    Connector(Arc<dyn Connector>), // in place of `Proxy`, TCP only
+    Subscribe(Subscriber),
+    Unsubscribe(SubscriberId),
+    Hold(SubscriberId, bool), // keep outputs back from a subscriber until released
 }
 
-#[derive(PartialEq, Eq, Debug)]
+fn or_none<T: Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "none".to_owned(),
+    }
+}
+
+impl Input {
+    // the setting changed by the command, named as in the repl
+    fn setting(&self) -> Option<(&'static str, String)> {
+        match self {
+            Input::Threads(count) => Some(("threads", count.to_string())),
+            Input::Stale(stale) => Some(("stale", stale.to_string())),
+            Input::Attmpts(count) => Some(("attemps", count.to_string())),
+            Input::TcpTimeout(milis) => Some(("tcp-timeout", milis.to_string())),
+            Input::UdpTimeout(milis) => Some(("udp-timeout", milis.to_string())),
+            Input::AuditLog(path) => Some(("audit-log", path.clone())),
+            Input::HostLimit(limit) => Some(("host-limit", limit.to_string())),
+            Input::SubnetLimit(limit) => Some(("subnet-limit", limit.to_string())),
+            Input::ResolveAll(value) => Some(("resolve-all", value.to_string())),
+            Input::DnsTtl(seconds) => Some(("dns-ttl", seconds.to_string())),
+            Input::ReverseDns(value) => Some(("reverse-dns", value.to_string())),
+            Input::RdnsTimeout(milis) => Some(("rdns-timeout", milis.to_string())),
+            Input::RdnsConcurrency(count) => Some(("rdns-concurrency", count.to_string())),
+            Input::SourceIp(ip) => Some(("source-ip", or_none(ip))),
+            Input::SourcePort(port) => Some(("source-port", or_none(port))),
+            Input::Interface(interface) => Some(("interface", or_none(interface))),
+            Input::Netns(name) => Some(("netns", or_none(name))),
+            Input::Proxy(url) => Some(("proxy", or_none(url))),
+            Input::Connector(_) => Some(("proxy", "custom".to_owned())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ScanResult {
+    pub host: String,
+    pub address: SocketAddr,
+    pub name: Option<String>, // reverse DNS
+    pub state: PortState,
+    pub attemps: usize,
+    pub latency: Option<Duration>, // until the target answered, `None` if it never did
+    pub error: Option<std::io::ErrorKind>, // behind a closed or unreachable state
+    pub time: SystemTime,          // when the probe finished
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Output {
-    // async
-    TcpScan(String, u16, PortState),
-    UdpScan(String, u16, PortState),
+    TcpScan(ScanResult),
+    UdpScan(ScanResult),
+    JobQueued(JobSummary),
+    JobStarted(JobId), // its first probe is under way
+    JobCompleted(JobSummary),
+    JobCancelled(JobSummary), // tallied up to the cancel
+    Paused,
+    Resumed,
+    ConfigChanged(String, String),
     Idle,
-    // sync
+    Error(Error),
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum Reply {
     Ok,
+    Subscribed(SubscriberId),
+    Threads(usize), // workers in the pool
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    Terminated,
+    MasterPanic,
+    WorkerPanic(WorkerId),
+    ProbeFailed(Protocol, String, SocketAddr),
+    Socket(Protocol, String, SocketAddr, std::io::ErrorKind), // no local socket for the probe
+    Resolve(String),
+    Family(String), // no address of the source ip's family
+    Spawn(std::io::ErrorKind),
+    Netns(std::io::ErrorKind),
+    Proxy(String),
+    AuditLog(std::io::ErrorKind),
+    UnknownSubscriber(SubscriberId),
+    Source(std::io::ErrorKind),
+    TooManyThreads(usize, usize), // requested, allowed
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Terminated => write!(f, "scanner has been terminated"),
+            Error::MasterPanic => write!(f, "scanner thread has panicked"),
+            Error::WorkerPanic(id) => write!(f, "worker #{} has panicked", id),
+            Error::ProbeFailed(protocol, host, address) => write!(
+                f,
+                "{:?} probe of {} has failed",
+                protocol,
+                format_address(host, address)
+            ),
+            Error::Socket(protocol, host, address, kind) => write!(
+                f,
+                "{:?} probe of {} has no local socket: {}",
+                protocol,
+                format_address(host, address),
+                kind
+            ),
+            Error::Resolve(host) => write!(f, "failed to resolve {}", host),
+            Error::Family(host) => write!(f, "{} has no address of the source ip's family", host),
+            Error::Spawn(kind) => write!(f, "failed to spawn worker: {}", kind),
+            Error::Netns(kind) => write!(f, "failed to enter network namespace: {}", kind),
+            Error::Proxy(reason) => write!(f, "proxy: {}", reason),
+            Error::AuditLog(kind) => write!(f, "audit log: {}", kind),
+            Error::UnknownSubscriber(id) => write!(f, "no subscriber #{}", id),
+            Error::Source(kind) => write!(f, "unusable probe source: {}", kind),
+            Error::TooManyThreads(count, max) => write!(
+                f,
+                "{} threads requested, the open file and thread limits allow {}",
+                count, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// the address alone when a literal was scanned, `host (address)` otherwise
+pub fn format_address(host: &str, address: &SocketAddr) -> String {
+    if host.parse::<IpAddr>() == Ok(address.ip()) {
+        address.to_string()
+    } else if address.ip().is_unspecified() {
+        // left to the proxy to resolve
+        format!("{}:{}", host, address.port())
+    } else {
+        format!("{} ({})", host, address)
+    }
+}
+
+impl Display for ScanResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:?}",
+            format_address(&self.host, &self.address),
+            self.state
+        )?;
+        if let Some(name) = &self.name {
+            write!(f, " ({})", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for JobSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let protocol = match self.protocol {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+        };
+        write!(f, "{} {} {}-{}", protocol, self.host, self.from, self.to)
+    }
+}
+
+impl JobSummary {
+    fn tally(&self) -> String {
+        let mut tally = format!(
+            "{} open, {} closed, {} unreachable",
+            self.open, self.closed, self.unreachable
+        );
+        if self.failed > 0 {
+            tally += &format!(", {} failed", self.failed);
+        }
+        tally
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Output::TcpScan(result) => write!(f, "tcp {}", result),
+            Output::UdpScan(result) => write!(f, "udp {}", result),
+            Output::JobQueued(job) => write!(f, "job #{} queued: {}", job.id, job),
+            Output::JobStarted(id) => write!(f, "job #{} started", id),
+            Output::JobCompleted(job) => {
+                write!(f, "job #{} completed: {}, {}", job.id, job, job.tally())
+            }
+            Output::JobCancelled(job) => {
+                write!(f, "job #{} cancelled: {}, {}", job.id, job, job.tally())
+            }
+            Output::Paused => write!(f, "Paused"),
+            Output::Resumed => write!(f, "Resumed"),
+            Output::ConfigChanged(name, value) => write!(f, "config {} = {}", name, value),
+            Output::Idle => write!(f, "Idle"),
+            Output::Error(err) => write!(f, "Error: {}", err),
+        }
+    }
 }
-struct ScanMaster<O: Fn(Output)> {
+
+struct ScanMaster {
     workers: Vec<WorkerHandle>,
     message_rx: Receiver<WorkerMessage>,
     message_tx: Sender<WorkerMessage>,
     ranges: ScanQueue,
     config: Arc<Mutex<ScannerConfig>>,
     state: ScannerState,
-    input_rx: Receiver<Input>,
-    output_tx: Sender<Output>,
-    output: O,
+    input_rx: Receiver<Request>,
+    deadline: Receiver<Instant>, // of a graceful shutdown
+    output_tx: Sender<Result<Reply, Error>>,
+    bus: Bus,
     id_counter: usize,
+    jobs: Vec<Job>,
+    job_counter: usize,
+    audit: AuditLog,
+    rdns: Arc<ReverseDns>,
+    prober: Arc<dyn Prober>,
+    initial_netns: Option<Namespace>,
 }
 
-impl<O: Fn(Output)> ScanMaster<O> {
-    fn new(output: O, input_rx: Receiver<Input>, output_tx: Sender<Output>) -> ScanMaster<O> {
+impl ScanMaster {
+    fn new(
+        bus: Bus,
+        prober: Arc<dyn Prober>,
+        config: Arc<Mutex<ScannerConfig>>,
+        input_rx: Receiver<Request>,
+        output_tx: Sender<Result<Reply, Error>>,
+    ) -> ScanMaster {
         let (message_tx, message_rx) = crossbeam::channel::unbounded();
         let workers = vec![];
-        let ranges = ScanQueue::new();
+        let ranges = ScanQueue::default();
         ScanMaster {
             workers,
             message_rx,
             message_tx,
             ranges,
-            config: Arc::new(Mutex::new(ScannerConfig::default())),
+            config,
             state: ScannerState::Running,
             input_rx,
-            output,
+            deadline: crossbeam::channel::never(),
+            bus,
             output_tx,
             id_counter: 0,
+            jobs: vec![],
+            job_counter: 0,
+            audit: AuditLog::new(),
+            rdns: Arc::new(ReverseDns::default()),
+            prober,
+            initial_netns: Namespace::current().ok(),
         }
     }
-    fn send_async_output(&self, output: Output) {
-        let cb = &self.output;
-        cb(output)
+    fn send_async_output(&mut self, output: Output) {
+        self.bus.publish(output);
+    }
+    // a record that could not be written is not dropped silently
+    fn audit_result(&mut self, rsl: std::io::Result<()>) {
+        if let Err(err) = rsl {
+            self.send_async_output(Output::Error(Error::AuditLog(err.kind())));
+        }
     }
-    fn send_sync_output(&self, output: Output) {
+    fn send_sync_output(&self, output: Result<Reply, Error>) {
         let _ = self.output_tx.send(output);
     }
     fn threads_clean(&mut self) {
@@ -315,88 +684,346 @@ impl<O: Fn(Output)> ScanMaster<O> {
             .filter(|wh| !wh.is_term())
             .collect::<Vec<_>>();
     }
-    fn try_close(&mut self, count: usize) {
+    // closes up to `count` idle workers, returns how many were closed
+    fn try_close(&mut self, count: usize) -> usize {
+        let requested = count;
         let mut count = count;
+        let mut errors = vec![];
         for wh in self.workers.iter_mut() {
             if count == 0 {
                 break;
             }
             if wh.is_idle() {
                 wh.state = WorkerState::Term;
-                wh.send_instruction(Instruction::Term);
-                wh.join();
+                let _ = wh.send_instruction(Instruction::Term);
+                if let Err(err) = wh.join() {
+                    errors.push(err);
+                }
                 count -= 1;
             }
         }
         self.threads_clean();
+        for err in errors {
+            self.send_async_output(Output::Error(err));
+        }
+        requested - count
+    }
+    // busy workers are told to end after their probe, and leave the pool
+    // right away
+    fn retire(&mut self, count: usize) {
+        let busy = count - self.try_close(count);
+        for wh in self
+            .workers
+            .iter_mut()
+            .filter(|wh| wh.state == WorkerState::Working)
+            .take(busy)
+        {
+            wh.state = WorkerState::Retiring;
+            let _ = wh.send_instruction(Instruction::Term);
+        }
+    }
+    fn pool_size(&self) -> usize {
+        self.workers
+            .iter()
+            .filter(|wh| wh.state == WorkerState::Idle || wh.state == WorkerState::Working)
+            .count()
+    }
+    // back to the pool with its probe done, or gone if it was retired
+    fn release(&mut self, idx: usize) {
+        let wh = &mut self.workers[idx];
+        wh.host = None;
+        wh.stale = false;
+        if wh.state != WorkerState::Retiring {
+            wh.state = WorkerState::Idle;
+            return;
+        }
+        wh.state = WorkerState::Term;
+        let rsl = wh.join();
+        self.threads_clean();
+        if let Err(err) = rsl {
+            self.send_async_output(Output::Error(err));
+        }
     }
     fn try_terminate(&mut self) {
         self.try_close(self.workers.len());
-        if self.workers.len() == 0 {
+        if self.workers.is_empty() {
             self.state = ScannerState::Terminated;
         }
     }
-    fn config(&mut self) -> MutexGuard<ScannerConfig> {
+    fn config(&mut self) -> MutexGuard<'_, ScannerConfig> {
         self.config.lock().unwrap()
     }
-    fn thread_count_control(&mut self) {
+    // brings the pool to the configured size, returns the size reached.
+    // when spawning fails the configured size drops to it
+    fn thread_count_control(&mut self) -> Result<usize, Error> {
         let expected_count = self.config().thread_count;
-        if expected_count > self.workers.len() {
-            let diff = expected_count - self.workers.len();
-            for _ in 0..diff {
-                self.spawn();
+        let size = self.pool_size();
+        if expected_count > size {
+            for _ in size..expected_count {
+                if let Err(err) = self.spawn() {
+                    self.config().thread_count = self.pool_size();
+                    self.assign_work();
+                    return Err(err);
+                }
             }
             self.assign_work();
-        } else if expected_count < self.workers.len() {
-            let diff = self.workers.len() - expected_count;
-            self.try_close(diff)
+        } else if expected_count < size {
+            self.retire(size - expected_count);
         }
+        Ok(self.pool_size())
     }
     fn handle_message(&mut self, message: WorkerMessage) {
+        let worker_idx = match self
+            .workers
+            .binary_search_by_key(&message.worker_id, |wh| wh.id)
+        {
+            Ok(idx) => idx,
+            Err(_) => return,
+        };
         match message.content {
-            Message::Scan(host, port, state) => {
-                let worker_id = message.worker_id;
-                let worker_idx = self
-                    .workers
-                    .binary_search_by_key(&worker_id, |wh| wh.id)
-                    .unwrap();
+            Message::Scan(job, (host, address, protocol), outcome, time) => {
+                let stale = self.workers[worker_idx].stale;
+                self.release(worker_idx);
+                if !stale || !self.config().stale {
+                    let result = ScanResult {
+                        host: host.to_string(),
+                        address,
+                        name: self.reverse_name(address.ip()),
+                        state: outcome.state,
+                        attemps: outcome.attemps,
+                        latency: outcome.latency,
+                        error: outcome.error,
+                        time,
+                    };
+                    match protocol {
+                        Protocol::Tcp => self.send_async_output(Output::TcpScan(result)),
+                        Protocol::Udp => self.send_async_output(Output::UdpScan(result)),
+                    }
+                }
+                self.finish_probe(job, Some(outcome.state));
+            }
+            Message::Cancelled(job) => {
+                self.release(worker_idx);
+                self.finish_probe(job, None);
+            }
+            Message::Netns(job, kind) => {
+                self.release(worker_idx);
+                // reported once, the scan is cancelled with it
+                if self.jobs.iter().any(|j| j.summary.id == job) {
+                    self.send_async_output(Output::Error(Error::Netns(kind)));
+                    self.cancel();
+                }
+                self.finish_probe(job, None);
+            }
+            Message::Failed(job, (host, address, protocol), failure) => {
+                let stale = self.workers[worker_idx].stale;
+                self.release(worker_idx);
+                match failure {
+                    // the same goes for every other probe
+                    Failure::Connector(reason) => {
+                        if self.jobs.iter().any(|j| j.summary.id == job) {
+                            self.send_async_output(Output::Error(Error::Proxy(reason)));
+                            self.cancel();
+                        }
+                    }
+                    // may pass, e.g. once descriptors are freed
+                    Failure::Socket(kind) => {
+                        if !stale || !self.config().stale {
+                            self.send_async_output(Output::Error(Error::Socket(
+                                protocol,
+                                host.to_string(),
+                                address,
+                                kind,
+                            )));
+                        }
+                    }
+                }
+                self.finish_probe(job, None);
+            }
+            Message::Panic(probe) => {
                 let worker = &mut self.workers[worker_idx];
-                worker.state = WorkerState::Idle;
+                let retiring = worker.state == WorkerState::Retiring;
+                worker.state = WorkerState::Term;
                 let stale = worker.stale;
-                worker.stale = false;
-                if !stale || !self.config().stale {
-                    match port.protocol {
-                        Protocol::Tcp => {
-                            self.send_async_output(Output::TcpScan(host, port.number, state))
+                let rsl = worker.join();
+                self.threads_clean();
+                match probe {
+                    Some((job, (host, address, protocol))) => {
+                        if !stale || !self.config().stale {
+                            self.send_async_output(Output::Error(Error::ProbeFailed(
+                                protocol,
+                                host.to_string(),
+                                address,
+                            )));
                         }
-                        Protocol::Udp => {
-                            self.send_async_output(Output::UdpScan(host, port.number, state))
+                        self.finish_probe(job, None);
+                    }
+                    None => {
+                        if let Err(err) = rsl {
+                            self.send_async_output(Output::Error(err));
                         }
                     }
                 }
-                if self.state == ScannerState::Running {
-                    self.thread_count_control();
-                    self.assign_work();
-                    self.check_idle();
-                } else if self.state == ScannerState::Ending {
-                    self.try_terminate();
+                // replace the lost worker right away, so that the scan keeps
+                // going even while paused
+                if self.state != ScannerState::Ending && !retiring {
+                    if let Err(err) = self.spawn() {
+                        self.send_async_output(Output::Error(err));
+                    }
                 }
             }
         }
+        if self.state == ScannerState::Running {
+            self.assign_work();
+            self.check_idle();
+        } else if self.state == ScannerState::Ending {
+            self.try_terminate();
+        }
+    }
+    fn enqueue(
+        &mut self,
+        host: String,
+        protocol: Protocol,
+        from: u16,
+        to: u16,
+        config: JobConfig,
+        addresses: Vec<SocketAddr>,
+    ) -> Result<(), Error> {
+        let host = net::strip_brackets(&host).to_owned();
+        self.job_counter += 1;
+        let shared: Host = host.as_str().into();
+        let ranges = addresses
+            .into_iter()
+            .map(|ip| AddressRange {
+                job: self.job_counter,
+                host: shared.clone(),
+                ip,
+                protocol,
+                from,
+                to,
+                config,
+            })
+            .collect::<Vec<_>>();
+        let rsl = self
+            .audit
+            .queued(self.job_counter, &ranges, &self.config.lock().unwrap());
+        self.audit_result(rsl);
+        let probes = ranges
+            .into_iter()
+            .map(|range| self.ranges.push(range))
+            .sum();
+        let summary = JobSummary {
+            id: self.job_counter,
+            host: host.clone(),
+            protocol,
+            from,
+            to,
+            probes,
+            open: 0,
+            closed: 0,
+            unreachable: 0,
+            failed: 0,
+        };
+        self.send_async_output(Output::JobQueued(summary.clone()));
+        if probes == 0 {
+            // an empty range
+            let rsl = self.audit.completed(summary.id);
+            self.audit_result(rsl);
+            self.send_async_output(Output::JobCompleted(summary));
+            self.check_idle();
+            return Ok(());
+        }
+        self.jobs.push(Job {
+            remaining: probes,
+            started: false,
+            summary,
+        });
+        self.assign_work();
+        Ok(())
+    }
+    fn start_job(&mut self, job: JobId) {
+        if let Some(j) = self.jobs.iter_mut().find(|j| j.summary.id == job) {
+            if !j.started {
+                j.started = true;
+                self.send_async_output(Output::JobStarted(job));
+            }
+        }
+    }
+    // `state` is `None` for a probe that failed or was aborted
+    fn finish_probe(&mut self, job: JobId, state: Option<PortState>) {
+        if let Some(idx) = self.jobs.iter().position(|j| j.summary.id == job) {
+            let j = &mut self.jobs[idx];
+            match state {
+                Some(PortState::Open) => j.summary.open += 1,
+                Some(PortState::Closed) => j.summary.closed += 1,
+                Some(PortState::Unreachable) => j.summary.unreachable += 1,
+                None => j.summary.failed += 1,
+            }
+            j.remaining -= 1;
+            if j.remaining == 0 {
+                let j = self.jobs.remove(idx);
+                let rsl = self.audit.completed(job);
+                self.audit_result(rsl);
+                self.send_async_output(Output::JobCompleted(j.summary));
+            }
+        }
+    }
+    fn cancel_jobs(&mut self) {
+        for job in std::mem::take(&mut self.jobs) {
+            let rsl = self.audit.cancelled(job.summary.id);
+            self.audit_result(rsl);
+            self.send_async_output(Output::JobCancelled(job.summary));
+        }
+    }
+    fn cancel(&mut self) {
+        self.cancel_jobs();
+        self.stale_all();
+        self.abort_all();
+        self.ranges.clear();
+        if self.config().stale {
+            self.bus.discard_results();
+        }
     }
     fn stale_all(&mut self) {
-        for wh in self.workers.iter_mut() {
+        // idle workers have nothing in flight, their next result is fresh
+        for wh in self.workers.iter_mut().filter(|wh| !wh.is_idle()) {
             wh.stale = true;
         }
     }
-    fn handle_input(&mut self, input: Input) {
+    // in-flight probes give up within milliseconds, whatever their timeout
+    fn abort_all(&mut self) {
+        for wh in self.workers.iter().filter(|wh| !wh.is_idle()) {
+            wh.cancel.cancel();
+        }
+    }
+    fn handle_input(&mut self, request: Request) {
+        let rsl = self.execute(request);
+        self.send_sync_output(rsl);
+    }
+    fn execute(&mut self, request: Request) -> Result<Reply, Error> {
+        let Request {
+            input,
+            addresses,
+            proxy,
+        } = request;
         if self.state == ScannerState::Ending || self.state == ScannerState::Terminated {
-            return;
+            return Err(Error::Terminated);
         }
+        let setting = input.setting();
+        let mut reply = Reply::Ok;
         match input {
             Input::End => {
                 self.state = ScannerState::Ending;
+                self.cancel_jobs();
                 self.stale_all();
+                self.abort_all();
+                self.try_terminate();
+            }
+            Input::Shutdown(milis) => {
+                self.state = ScannerState::Ending;
+                self.cancel_jobs();
+                self.ranges.clear();
+                self.deadline = crossbeam::channel::after(Duration::from_millis(milis as u64));
                 self.try_terminate();
             }
             Input::Ping => {}
@@ -409,146 +1036,436 @@ impl<O: Fn(Output)> ScanMaster<O> {
             Input::UdpTimeout(milis) => {
                 self.config().udp_timeout = milis;
             }
-            Input::Cancel => {
-                self.stale_all();
-                self.ranges.clear();
-            }
+            Input::Cancel => self.cancel(),
             Input::Stale(stale) => {
                 self.config().stale = stale;
             }
-            Input::TcpRange(host, from, to) => {
-                self.ranges.push(AddressRange {
-                    host,
-                    protocol: Protocol::Tcp,
-                    from,
-                    to,
-                });
+            Input::HostLimit(limit) => {
+                self.config().host_limit = limit;
                 self.assign_work();
             }
-            Input::UdpRange(host, from, to) => {
-                self.ranges.push(AddressRange {
-                    host,
-                    protocol: Protocol::Udp,
-                    from,
-                    to,
-                });
+            Input::SubnetLimit(limit) => {
+                self.config().subnet_limit = limit;
                 self.assign_work();
             }
+            Input::TcpRange(host, from, to, config) => {
+                self.enqueue(host, Protocol::Tcp, from, to, config, addresses)?;
+            }
+            Input::UdpRange(host, from, to, config) => {
+                self.enqueue(host, Protocol::Udp, from, to, config, addresses)?;
+            }
+            Input::ResolveAll(value) => {
+                self.config().resolve_all = value;
+            }
+            Input::DnsTtl(seconds) => {
+                self.config().dns_ttl = seconds;
+            }
+            Input::ReverseDns(value) => {
+                self.config().reverse_dns = value;
+            }
+            Input::RdnsTimeout(milis) => {
+                self.config().rdns_timeout = milis;
+            }
+            Input::RdnsConcurrency(count) => {
+                self.config().rdns_concurrency = count;
+            }
+            Input::SourceIp(ip) => {
+                let mut source = self.config().source.clone();
+                source.ip = ip;
+                self.set_source(source)?;
+            }
+            Input::SourcePort(port) => {
+                let mut source = self.config().source.clone();
+                source.port = port;
+                self.set_source(source)?;
+            }
+            Input::Interface(interface) => {
+                let mut source = self.config().source.clone();
+                source.interface = interface;
+                self.set_source(source)?;
+            }
+            Input::Proxy(url) => {
+                let connector: Arc<dyn Connector> = match (url, proxy) {
+                    (Some(_), Some(proxy)) => Arc::new(proxy),
+                    (Some(url), None) => return Err(Error::Proxy(format!("invalid url {}", url))),
+                    (None, _) => Arc::new(Direct),
+                };
+                self.config().connector = connector;
+            }
+            Input::Connector(connector) => {
+                self.config().connector = connector;
+            }
+            Input::Netns(name) => {
+                let netns = match name {
+                    Some(name) => Some(Namespace::open(&name).map_err(|e| Error::Netns(e.kind()))?),
+                    None => None,
+                };
+                // the interface has to exist in the new namespace too
+                let source = self.config().source.clone();
+                check_source(&source, netns.clone())?;
+                self.config().netns = netns;
+            }
+            Input::AuditLog(path) => {
+                self.audit
+                    .open(&path)
+                    .map_err(|e| Error::AuditLog(e.kind()))?;
+            }
             Input::Stop => {
                 if self.state == ScannerState::Running {
                     self.state = ScannerState::Stop;
+                    self.send_async_output(Output::Paused);
                 }
             }
             Input::Cont => {
-                if self.state == ScannerState::Stop {
+                let resumed = self.state == ScannerState::Stop;
+                if resumed {
                     self.state = ScannerState::Running;
+                    self.send_async_output(Output::Resumed);
                 }
                 self.assign_work();
+                // whatever finished while paused went unreported
+                if resumed {
+                    self.check_idle();
+                }
             }
             Input::Threads(count) => {
+                // retiring workers keep their thread until their probe is done
+                let retiring = self
+                    .workers
+                    .iter()
+                    .filter(|wh| wh.state == WorkerState::Retiring)
+                    .count();
+                let max = limits::max_workers().saturating_sub(retiring);
+                if count > max {
+                    return Err(Error::TooManyThreads(count, max));
+                }
                 self.config().thread_count = count;
-                self.thread_count_control();
+                reply = Reply::Threads(self.thread_count_control()?);
+            }
+            Input::Subscribe(subscriber) => {
+                return Ok(Reply::Subscribed(self.bus.subscribe(subscriber)));
+            }
+            Input::Unsubscribe(id) => {
+                if !self.bus.unsubscribe(id) {
+                    return Err(Error::UnknownSubscriber(id));
+                }
+            }
+            Input::Hold(id, hold) => {
+                if !self.bus.hold(id, hold) {
+                    return Err(Error::UnknownSubscriber(id));
+                }
             }
             Input::NOP => {}
         }
-        self.send_sync_output(Output::Ok);
+        if let Some((name, value)) = setting {
+            self.send_async_output(Output::ConfigChanged(name.to_owned(), value));
+        }
+        Ok(reply)
+    }
+    fn set_source(&mut self, source: Source) -> Result<(), Error> {
+        let netns = self.config().netns.clone();
+        check_source(&source, netns)?;
+        self.config().source = source;
+        Ok(())
+    }
+    fn reverse_name(&mut self, ip: IpAddr) -> Option<String> {
+        let config = self.config();
+        if !config.reverse_dns || ip.is_unspecified() {
+            return None;
+        }
+        let timeout = Duration::from_millis(config.rdns_timeout as u64);
+        let concurrency = config.rdns_concurrency;
+        drop(config);
+        self.rdns.name(ip, timeout, concurrency)
     }
-    fn spawn(&mut self) {
+    fn spawn(&mut self) -> Result<(), Error> {
         self.id_counter += 1;
         let id = self.id_counter;
         let (work_tx, work_rx) = crossbeam::channel::bounded(1);
         let message_tx = self.message_tx.clone();
         let config = self.config.clone();
-        let handle = WorkerHandle {
-            id: self.id_counter,
-            work_tx,
-            state: WorkerState::Idle,
-            join_handle: Some(std::thread::spawn(move || {
-                let worker = Worker {
+        let prober = self.prober.clone();
+        let initial_netns = self.initial_netns.clone();
+        let join_handle = std::thread::Builder::new()
+            .spawn(move || {
+                let mut worker = Worker {
                     id,
                     work_rx,
                     message_tx,
                     config,
+                    prober,
+                    probe: None,
+                    netns: None,
+                    initial_netns,
                 };
                 worker.run();
-            })),
+            })
+            .map_err(|e| Error::Spawn(e.kind()))?;
+        let handle = WorkerHandle {
+            id: self.id_counter,
+            work_tx,
+            state: WorkerState::Idle,
+            host: None,
+            cancel: Cancellation::default(),
+            join_handle: Some(join_handle),
             stale: false,
         };
         self.workers.push(handle);
+        Ok(())
     }
     fn assign_work(&mut self) {
         if self.state != ScannerState::Running {
             return;
         }
-        let mut ranges = std::mem::take(&mut self.ranges);
-        for wh in self.workers.iter_mut() {
-            if wh.is_idle() {
-                if let Some(address) = ranges.pop() {
-                    wh.send_instruction(Instruction::Scan(address));
-                    wh.state = WorkerState::Working;
-                } else {
-                    break;
-                }
+        let config = self.config();
+        let (host_limit, subnet_limit) = (config.host_limit, config.subnet_limit);
+        drop(config);
+        for idx in 0..self.workers.len() {
+            if !self.workers[idx].is_idle() {
+                continue;
+            }
+            let workers = &self.workers;
+            let available = |host: &str, ip: IpAddr| {
+                let busy = workers.iter().filter_map(|wh| wh.host.as_ref());
+                // a name left to the proxy has no address of its own
+                let same = |(h, i): &&(Host, IpAddr)| {
+                    if ip.is_unspecified() {
+                        i.is_unspecified() && **h == *host
+                    } else {
+                        *i == ip
+                    }
+                };
+                let host_ok = host_limit == 0 || busy.clone().filter(same).count() < host_limit;
+                let subnet_ok = match subnet(ip) {
+                    Some(net) if subnet_limit > 0 => {
+                        busy.filter(|(_, h)| subnet(*h) == Some(net)).count() < subnet_limit
+                    }
+                    _ => true,
+                };
+                host_ok && subnet_ok
+            };
+            // every idle worker asks the queue again, so a newly queued job
+            // of higher priority takes the very next free worker
+            if let Some((job, address, config)) = self.ranges.pop(available) {
+                let wh = &mut self.workers[idx];
+                wh.host = Some((address.0.clone(), address.1.ip()));
+                wh.cancel = Cancellation::default();
+                let cancel = wh.cancel.clone();
+                // a failed send means the worker is gone, its panic is
+                // reported once the `Message::Panic` is received
+                let _ = wh.send_instruction(Instruction::Scan(job, address, config, cancel));
+                wh.state = WorkerState::Working;
+                self.start_job(job);
+            } else {
+                break;
             }
         }
-        self.ranges = ranges;
     }
-    fn check_idle(&self) {
+    fn check_idle(&mut self) {
         if self.workers.iter().filter(|wh| wh.is_idle()).count() == self.workers.len()
             && self.state == ScannerState::Running
-            && self.ranges.len() == 0
+            && self.ranges.is_empty()
         {
             self.send_async_output(Output::Idle)
         }
     }
+    // every `Scanner` is gone, nobody is left to end the scan
     fn drop_input_channel(&mut self) {
         self.input_rx = crossbeam::channel::never();
+        let _ = self.execute(Input::End.into());
     }
     fn listen(&mut self) {
         let message_rx = self.message_rx.clone();
-        let input_rx = self.input_rx.clone();
         while self.state != ScannerState::Terminated {
+            let input_rx = self.input_rx.clone();
+            let deadline = self.deadline.clone();
             select! {
-                recv(message_rx) -> message => self.handle_message(message.expect(
-                    "FATAL: Scanner failed to receive message. \
-            The thread has been probably terminated too early, or either the recv call is late.")),
+                recv(message_rx) -> message => if let Ok(message) = message {
+                    self.handle_message(message)
+                },
                 recv(input_rx) -> input => match input {
                     Err(_) => self.drop_input_channel(),
                     Ok(input) => self.handle_input(input),
                 },
+                recv(deadline) -> _ => {
+                    self.deadline = crossbeam::channel::never();
+                    self.abort_all();
+                },
             };
         }
     }
 }
 
+// tried in a throwaway thread inside the namespace the workers use
+fn check_source(source: &Source, netns: Option<Namespace>) -> Result<(), Error> {
+    let source = source.clone();
+    std::thread::spawn(move || {
+        if let Some(netns) = netns {
+            netns.enter()?;
+        }
+        source.check()
+    })
+    .join()
+    .unwrap_or_else(|_| Err(std::io::ErrorKind::Other.into()))
+    .map_err(|e| Error::Source(e.kind()))
+}
+
+// the master thread, joined once the last `Scanner` clone is dropped
+struct MasterHandle(Mutex<Option<JoinHandle<()>>>);
+
+impl Drop for MasterHandle {
+    fn drop(&mut self) {
+        let handle = match self.0.get_mut() {
+            Ok(handle) => handle.take(),
+            Err(_) => return,
+        };
+        if let Some(handle) = handle {
+            // dropped by the output callback, the master can not wait for itself
+            if handle.thread().id() != std::thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+// dropping the last clone ends the scan, the closed input channel
+// tells the master to stop
 #[derive(Clone)]
 pub struct Scanner {
-    tx: Sender<Input>,
-    rx: Receiver<Output>,
-    handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    tx: Sender<Request>,
+    rx: Receiver<Result<Reply, Error>>,
+    handle: Arc<MasterHandle>,
+    config: Arc<Mutex<ScannerConfig>>,
+    dns: Arc<DnsCache>,
+}
+
+// names are resolved by the caller before the command reaches the
+// master, a slow lookup does not hold up the scan
+// what the master needs to carry out `input`, resolved by the caller
+// so that lookups never block the master
+struct Request {
+    input: Input,
+    addresses: Vec<SocketAddr>, // those of a queued range
+    proxy: Option<Proxy>,
+}
+
+impl From<Input> for Request {
+    fn from(input: Input) -> Self {
+        Request {
+            input,
+            addresses: vec![],
+            proxy: None,
+        }
+    }
 }
 
 impl Scanner {
     pub fn new<O: Fn(Output) + Send + 'static>(output: O) -> Scanner {
+        Scanner::with_prober(SocketProber, output)
+    }
+    // probes go through `prober` instead of real sockets
+    pub fn with_prober<P: Prober + 'static, O: Fn(Output) + Send + 'static>(
+        prober: P,
+        output: O,
+    ) -> Scanner {
         let (input_tx, input_rx) = crossbeam::channel::unbounded();
         let (output_tx, output_rx) = crossbeam::channel::unbounded();
-        let mut scan_master = ScanMaster::new(output, input_rx, output_tx);
+        let mut bus = Bus::default();
+        bus.subscribe(Subscriber::new(output));
+        let config = Arc::new(Mutex::new(ScannerConfig::default()));
+        let mut scan_master =
+            ScanMaster::new(bus, Arc::new(prober), config.clone(), input_rx, output_tx);
         let handle = std::thread::spawn(move || {
-            scan_master.thread_count_control();
+            if let Err(err) = scan_master.thread_count_control() {
+                scan_master.send_async_output(Output::Error(err));
+            }
             scan_master.listen();
         });
-        let handle = Arc::new(Mutex::new(Some(handle)));
+        let handle = Arc::new(MasterHandle(Mutex::new(Some(handle))));
         Scanner {
             tx: input_tx,
             rx: output_rx,
             handle,
+            config,
+            dns: Arc::new(DnsCache::default()),
         }
     }
-    pub fn command(&self, input: Input) -> Option<Output> {
-        self.tx.send(input).ok()?;
-        self.rx.recv().ok()
+    pub fn command(&self, input: Input) -> Result<Reply, Error> {
+        let addresses = match &input {
+            Input::TcpRange(host, ..) => {
+                self.dns.job_addresses(host, Protocol::Tcp, &self.config)?
+            }
+            Input::UdpRange(host, ..) => {
+                self.dns.job_addresses(host, Protocol::Udp, &self.config)?
+            }
+            _ => vec![],
+        };
+        let proxy = match &input {
+            Input::Proxy(Some(url)) => Some(
+                Proxy::parse(url, |host| host.to_socket_addrs().ok()?.next())
+                    .ok_or(Error::Proxy(format!("invalid url {}", url)))?,
+            ),
+            _ => None,
+        };
+        let request = Request {
+            input,
+            addresses,
+            proxy,
+        };
+        self.tx.send(request).map_err(|_| Error::Terminated)?;
+        self.rx.recv().map_err(|_| Error::Terminated)?
+    }
+    pub fn join(&self) -> Result<(), Error> {
+        let handle = self.handle.0.lock().map_err(|_| Error::MasterPanic)?.take();
+        match handle {
+            Some(handle) => handle.join().map_err(|_| Error::MasterPanic),
+            None => Ok(()),
+        }
     }
-    pub fn join(&self) -> Option<()> {
-        self.handle.lock().ok()?.take()?.join().ok()
+    pub fn subscribe(&self, subscriber: Subscriber) -> Result<SubscriberId, Error> {
+        match self.command(Input::Subscribe(subscriber))? {
+            Reply::Subscribed(id) => Ok(id),
+            _ => Err(Error::Terminated),
+        }
+    }
+    // resizes the worker pool, returns the size it has reached
+    pub fn threads(&self, count: usize) -> Result<usize, Error> {
+        match self.command(Input::Threads(count))? {
+            Reply::Threads(size) => Ok(size),
+            _ => Err(Error::Terminated),
+        }
+    }
+    pub fn unsubscribe(&self, id: SubscriberId) -> Result<(), Error> {
+        self.command(Input::Unsubscribe(id)).map(|_| ())
+    }
+    pub fn settings(&self) -> Result<Settings, Error> {
+        let config = self.config.lock().map_err(|_| Error::MasterPanic)?;
+        Ok(Settings {
+            thread_count: config.thread_count,
+            stale: config.stale,
+            tcp_timeout: config.tcp_timeout,
+            udp_timeout: config.udp_timeout,
+            attemps: config.attemps,
+            host_limit: config.host_limit,
+            subnet_limit: config.subnet_limit,
+            resolve_all: config.resolve_all,
+            dns_ttl: config.dns_ttl,
+            reverse_dns: config.reverse_dns,
+            rdns_timeout: config.rdns_timeout,
+            rdns_concurrency: config.rdns_concurrency,
+            source_ip: config.source.ip,
+            source_port: config.source.port,
+            interface: config.source.interface.clone(),
+            netns: config.netns.as_ref().map(|ns| ns.name().to_owned()),
+            connector: config.connector.describe(),
+        })
+    }
+    // stops taking work and lets the probes in flight finish, whatever
+    // is still running after `milis` is aborted
+    pub fn shutdown(&self, milis: usize) -> Result<(), Error> {
+        match self.command(Input::Shutdown(milis)) {
+            Ok(_) | Err(Error::Terminated) => self.join(),
+            Err(err) => Err(err),
+        }
     }
 }
@@ -0,0 +1,45 @@
+// file descriptors kept for the process itself: stdio, channels, the audit
+// log, dns and reverse dns lookups
+const RESERVED_FDS: usize = 64;
+// threads kept for the master, the caller and reverse dns lookups
+const RESERVED_THREADS: usize = 16;
+
+// every worker holds a socket while probing and is a thread of its own,
+// so the pool may not outgrow either limit
+#[cfg(unix)]
+pub fn max_workers() -> usize {
+    let files = soft_limit(|limit| unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, limit) });
+    let files = files.saturating_sub(RESERVED_FDS);
+    let threads = thread_limit().saturating_sub(RESERVED_THREADS);
+    files.min(threads)
+}
+
+#[cfg(not(unix))]
+pub fn max_workers() -> usize {
+    usize::MAX
+}
+
+// the resource constant's type differs between platforms, so the caller
+// does the `getrlimit`
+#[cfg(unix)]
+fn soft_limit(get: impl FnOnce(&mut libc::rlimit) -> libc::c_int) -> usize {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if get(&mut limit) != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return usize::MAX;
+    }
+    usize::try_from(limit.rlim_cur).unwrap_or(usize::MAX)
+}
+
+// threads count against the user's processes, and the kernel has
+// a limit of its own
+#[cfg(unix)]
+fn thread_limit() -> usize {
+    let system = std::fs::read_to_string("/proc/sys/kernel/threads-max")
+        .ok()
+        .and_then(|max| max.trim().parse().ok())
+        .unwrap_or(usize::MAX);
+    soft_limit(|limit| unsafe { libc::getrlimit(libc::RLIMIT_NPROC, limit) }).min(system)
+}
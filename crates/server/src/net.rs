@@ -1,52 +1,393 @@
 use std::{
-    net::{TcpStream, ToSocketAddrs, UdpSocket},
-    time::Duration,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
 };
 
-pub fn scan_tcp(host: String, number: u16, timeout: Duration, attemps: usize) -> Option<bool> {
-    let mut rsl = None;
-    for _ in 0..attemps {
-        rsl = try_tcp(host.clone(), number, timeout);
-        if rsl == Some(true) {
-            return rsl;
+use socket2::{Domain, Socket, Type};
+
+use crate::{probe::Failure, Cancellation, Outcome, PortState};
+
+// how often blocking waits look for a cancellation
+pub const SLICE: Duration = Duration::from_millis(10);
+
+#[cfg(unix)]
+fn scope_id(scope: &str) -> Option<u32> {
+    if let Ok(id) = scope.parse::<u32>() {
+        return Some(id);
+    }
+    let name = std::ffi::CString::new(scope).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        id => Some(id),
+    }
+}
+
+#[cfg(not(unix))]
+fn scope_id(scope: &str) -> Option<u32> {
+    scope.parse::<u32>().ok()
+}
+
+// `[::1]` -> `::1`
+pub fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+// accepts host names as well as IPv4 and IPv6 literals, the latter optionally
+// in brackets and with a `%scope` suffix for link-local addresses.
+// the returned addresses carry port 0
+pub fn lookup(host: &str) -> Option<Vec<SocketAddr>> {
+    let host = strip_brackets(host);
+    if let Some((ip, scope)) = host.split_once('%') {
+        let ip = ip.parse::<Ipv6Addr>().ok()?;
+        return Some(vec![SocketAddr::V6(SocketAddrV6::new(
+            ip,
+            0,
+            0,
+            scope_id(scope)?,
+        ))]);
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Some(vec![SocketAddr::new(ip, 0)]);
+    }
+    let mut addresses = vec![];
+    for address in (host, 0).to_socket_addrs().ok()? {
+        if !addresses.contains(&address) {
+            addresses.push(address);
         }
     }
-    rsl
+    if addresses.is_empty() {
+        None
+    } else {
+        Some(addresses)
+    }
+}
+
+// where outgoing probes are sent from, unset fields are left to the kernel
+#[derive(Default, Clone)]
+pub struct Source {
+    pub ip: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub interface: Option<String>,
+}
+
+impl Source {
+    // a socket is opened and bound the way probes do it, a source the
+    // kernel refuses is reported once instead of failing every probe
+    pub fn check(&self) -> io::Result<()> {
+        let target = match self.ip {
+            Some(IpAddr::V6(_)) => SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 0),
+            _ => SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0),
+        };
+        socket(&target, Type::STREAM, self).map(|_| ())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket
+        .bind_device(Some(interface.as_bytes()))
+        .map_err(|e| match e.raw_os_error() {
+            // has no kind of its own
+            Some(libc::ENODEV) => io::Error::new(io::ErrorKind::NotFound, e),
+            _ => e,
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_device(_socket: &Socket, _interface: &str) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+// a source address of the other family than `address` fails to bind
+pub fn socket(address: &SocketAddr, kind: Type, source: &Source) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(*address), kind, None)?;
+    if let Some(interface) = &source.interface {
+        bind_device(&socket, interface)?;
+    }
+    if source.port.is_some() {
+        // probes run in parallel from the same port
+        socket.set_reuse_address(true)?;
+    }
+    let unspecified = if address.is_ipv6() {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    };
+    let local = SocketAddr::new(source.ip.unwrap_or(unspecified), source.port.unwrap_or(0));
+    socket.bind(&local.into())?;
+    Ok(socket)
 }
-pub fn scan_udp(host: String, number: u16, timeout: Duration, attemps: usize) -> Option<bool> {
-    let mut rsl = None;
-    for _ in 0..attemps {
-        rsl = try_udp(host.clone(), number, timeout);
-        if rsl == Some(true) {
-            return rsl;
+
+pub fn scan_tcp(
+    host: &str,
+    address: SocketAddr,
+    timeout: Duration,
+    attemps: usize,
+    source: &Source,
+    connector: &dyn Connector,
+    cancel: &Cancellation,
+) -> Outcome {
+    retry(attemps, cancel, || {
+        match connector.connect(host, address, timeout, source, cancel) {
+            Ok(()) => Ok((PortState::Open, None)),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                Ok((PortState::Closed, Some(e.kind())))
+            }
+            Err(e) => {
+                let inner = e.get_ref();
+                if let Some(ConnectorFailure(reason)) = inner.and_then(|e| e.downcast_ref()) {
+                    Err(Failure::Connector(reason.clone()))
+                } else if let Some(SocketFailure(kind)) = inner.and_then(|e| e.downcast_ref()) {
+                    Err(Failure::Socket(*kind))
+                } else {
+                    Ok((PortState::Unreachable, Some(e.kind())))
+                }
+            }
+        }
+    })
+}
+pub fn scan_udp(
+    address: SocketAddr,
+    timeout: Duration,
+    attemps: usize,
+    source: &Source,
+    cancel: &Cancellation,
+) -> Outcome {
+    retry(attemps, cancel, || {
+        try_udp(address, timeout, source, cancel)
+    })
+}
+
+// repeats `attempt` until the port is found open, the attemps run out
+// or the probe fails
+fn retry(
+    attemps: usize,
+    cancel: &Cancellation,
+    mut attempt: impl FnMut() -> Result<(PortState, Option<io::ErrorKind>), Failure>,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    for n in 1..=attemps {
+        if cancel.is_cancelled() {
+            break;
         }
+        let start = Instant::now();
+        let (state, error) = match attempt() {
+            Ok(answer) => answer,
+            Err(failure) => {
+                return Outcome {
+                    attemps: n,
+                    failure: Some(failure),
+                    ..Outcome::default()
+                }
+            }
+        };
+        let answered = state == PortState::Open || error == Some(io::ErrorKind::ConnectionRefused);
+        outcome = Outcome {
+            state,
+            attemps: n,
+            latency: answered.then(|| start.elapsed()),
+            error,
+            failure: None,
+        };
+        if state == PortState::Open {
+            break;
+        }
+    }
+    outcome
+}
+
+// how a TCP probe reaches its target. a refused connection means closed,
+// an error made by `connector_failure` stops the scan, one made by
+// `socket_failure` fails the probe, any other error means unreachable
+pub trait Connector: Send + Sync {
+    fn connect(
+        &self,
+        host: &str,
+        address: SocketAddr,
+        timeout: Duration,
+        source: &Source,
+        cancel: &Cancellation,
+    ) -> io::Result<()>;
+    // whether unresolved names can be handed over, see `format_address`
+    fn remote_dns(&self) -> bool {
+        false
     }
-    rsl
+    // for the audit log and reports, without secrets
+    fn describe(&self) -> String {
+        "custom".to_owned()
+    }
+    // whether the socket bound to `Source` goes to the target itself.
+    // addresses of another family than the source ip are not scanned then
+    fn direct(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for dyn Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connector")
+    }
+}
+
+pub struct Direct;
+
+impl Connector for Direct {
+    fn connect(
+        &self,
+        _host: &str,
+        address: SocketAddr,
+        timeout: Duration,
+        source: &Source,
+        cancel: &Cancellation,
+    ) -> io::Result<()> {
+        let socket = socket(&address, Type::STREAM, source).map_err(socket_failure)?;
+        connect(&socket, &address, timeout, cancel)
+    }
+    fn describe(&self) -> String {
+        "none".to_owned()
+    }
+    fn direct(&self) -> bool {
+        true
+    }
+}
+
+// the connector could not make the probe at all, e.g. a proxy that is down
+// or rejects the credentials. says nothing about the target, the scan is
+// cancelled and `reason` reported as `Error::Proxy`
+pub fn connector_failure(reason: impl Into<String>) -> io::Error {
+    io::Error::other(ConnectorFailure(reason.into()))
+}
+
+#[derive(Debug)]
+struct ConnectorFailure(String);
+
+impl fmt::Display for ConnectorFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConnectorFailure {}
+
+// no local socket could be opened or bound, e.g. out of file descriptors.
+// says nothing about the target, the probe is reported as `Error::Socket`
+pub fn socket_failure(error: io::Error) -> io::Error {
+    io::Error::other(SocketFailure(error.kind()))
+}
+
+#[derive(Debug)]
+struct SocketFailure(io::ErrorKind);
+
+impl fmt::Display for SocketFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "local socket: {}", self.0)
+    }
+}
+
+impl std::error::Error for SocketFailure {}
+
+pub fn cancelled() -> io::Error {
+    io::Error::other("probe cancelled")
 }
 
-fn try_tcp(host: String, number: u16, timeout: Duration) -> Option<bool> {
-    let address = format!("{}:{}", host, number)
-        .to_socket_addrs()
-        .ok()?
-        .next()?;
-    match TcpStream::connect_timeout(&address, timeout) {
-        Ok(_) => Some(true),
-        Err(e) => match e.kind() {
-            std::io::ErrorKind::ConnectionRefused => Some(false),
-            _ => None,
+#[cfg(unix)]
+fn poll_writable(socket: &Socket, timeout: Duration) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+    let mut fd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().max(1) as libc::c_int;
+    match unsafe { libc::poll(&mut fd, 1, timeout) } {
+        -1 => match io::Error::last_os_error() {
+            e if e.kind() == io::ErrorKind::Interrupted => Ok(false),
+            e => Err(e),
         },
+        0 => Ok(false),
+        _ => Ok(true),
     }
 }
-fn try_udp(host: String, number: u16, timeout: Duration) -> Option<bool> {
-    let address = format!("{}:{}", host, number);
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    if socket.send_to(&[], address).is_err() {
-        return None;
+
+// `connect_timeout` that gives up as soon as the probe is cancelled
+#[cfg(unix)]
+pub fn connect(
+    socket: &Socket,
+    address: &SocketAddr,
+    timeout: Duration,
+    cancel: &Cancellation,
+) -> io::Result<()> {
+    socket.set_nonblocking(true)?;
+    match socket.connect(&(*address).into()) {
+        Ok(()) => {}
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {
+            let deadline = Instant::now() + timeout;
+            loop {
+                if cancel.is_cancelled() {
+                    return Err(cancelled());
+                }
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                if poll_writable(socket, SLICE.min(deadline - now))? {
+                    break;
+                }
+            }
+            if let Some(e) = socket.take_error()? {
+                return Err(e);
+            }
+        }
+        Err(e) => return Err(e),
+    }
+    socket.set_nonblocking(false)
+}
+
+#[cfg(not(unix))]
+pub fn connect(
+    socket: &Socket,
+    address: &SocketAddr,
+    timeout: Duration,
+    _cancel: &Cancellation,
+) -> io::Result<()> {
+    socket.connect_timeout(&(*address).into(), timeout)
+}
+
+fn try_udp(
+    address: SocketAddr,
+    timeout: Duration,
+    source: &Source,
+    cancel: &Cancellation,
+) -> Result<(PortState, Option<io::ErrorKind>), Failure> {
+    let socket = match socket(&address, Type::DGRAM, source) {
+        Ok(socket) => UdpSocket::from(socket),
+        Err(e) => return Err(Failure::Socket(e.kind())),
+    };
+    if let Err(e) = socket.send_to(&[], address) {
+        return Ok((PortState::Unreachable, Some(e.kind())));
     }
     let mut buffer = [];
-    socket.set_read_timeout(Some(timeout)).unwrap();
-    match socket.recv(&mut buffer) {
-        Ok(_) => Some(true),
-        Err(_) => Some(false),
+    let deadline = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        // no answer is taken for closed
+        if now >= deadline || cancel.is_cancelled() {
+            return Ok((PortState::Closed, Some(io::ErrorKind::TimedOut)));
+        }
+        socket
+            .set_read_timeout(Some(SLICE.min(deadline - now)))
+            .unwrap();
+        match socket.recv(&mut buffer) {
+            Ok(_) => return Ok((PortState::Open, None)),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Ok((PortState::Closed, Some(e.kind()))),
+        }
     }
 }
@@ -0,0 +1,58 @@
+use std::{fs::File, io, sync::Arc};
+
+// an open handle to a network namespace, workers enter it with `setns`
+// before creating their sockets
+#[derive(Clone)]
+pub struct Namespace {
+    file: Arc<File>,
+    name: Arc<str>,
+}
+
+impl PartialEq for Namespace {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file)
+    }
+}
+
+impl Namespace {
+    // a namespace created by `ip netns add <name>`
+    pub fn open(name: &str) -> io::Result<Namespace> {
+        // a path would open whatever file it points to
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let ns = Namespace {
+            file: Arc::new(File::open(format!("/var/run/netns/{}", name))?),
+            name: name.into(),
+        };
+        // tried in a throwaway thread, so that missing privileges are
+        // reported here instead of by every worker
+        let test = ns.clone();
+        std::thread::spawn(move || test.enter())
+            .join()
+            .unwrap_or_else(|_| Err(io::ErrorKind::Other.into()))?;
+        Ok(ns)
+    }
+    // the namespace of the calling thread
+    pub fn current() -> io::Result<Namespace> {
+        Ok(Namespace {
+            file: Arc::new(File::open("/proc/thread-self/ns/net")?),
+            name: "current".into(),
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[cfg(target_os = "linux")]
+    pub fn enter(&self) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+        match unsafe { libc::setns(self.file.as_raw_fd(), libc::CLONE_NEWNET) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn enter(&self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
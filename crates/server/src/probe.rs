@@ -0,0 +1,120 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    net::{self, Connector, Source},
+    PortState,
+};
+
+// everything a single probe of one port needs, taken from the global
+// `ScannerConfig` and the job's `JobConfig`
+pub struct Probe<'a> {
+    pub host: &'a str,       // as given by the user
+    pub address: SocketAddr, // unspecified when the connector resolves `host` itself
+    pub timeout: Duration,
+    pub attemps: usize,
+    pub source: &'a Source,
+    pub connector: &'a dyn Connector, // TCP only
+    pub cancel: &'a Cancellation,
+}
+
+// set by the master once the probe's job is cancelled or the scanner ends,
+// probers are expected to give up as soon as they notice
+#[derive(Clone, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// what a probe found out about a port
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Outcome {
+    pub state: PortState,
+    pub attemps: usize,               // used, at most `Probe::attemps`
+    pub latency: Option<Duration>,    // of the attempt the target answered
+    pub error: Option<io::ErrorKind>, // behind a closed or unreachable state
+    pub failure: Option<Failure>,     // the probe could not be made
+}
+
+// why a probe was not made, says nothing about the port
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Failure {
+    Connector(String),     // see `connector_failure`, the scan is cancelled
+    Socket(io::ErrorKind), // no local socket, see `socket_failure`
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Outcome {
+            state: PortState::Unreachable,
+            attemps: 0,
+            latency: None,
+            error: None,
+            failure: None,
+        }
+    }
+}
+
+// the network layer used by the workers
+pub trait Prober: Send + Sync {
+    fn tcp(&self, probe: &Probe) -> Outcome;
+    fn udp(&self, probe: &Probe) -> Outcome;
+    // for reports
+    fn describe(&self) -> String {
+        "custom".to_owned()
+    }
+}
+
+impl<P: Prober + ?Sized> Prober for Arc<P> {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        (**self).tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        (**self).udp(probe)
+    }
+    fn describe(&self) -> String {
+        (**self).describe()
+    }
+}
+
+// real sockets, the default
+pub struct SocketProber;
+
+impl Prober for SocketProber {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        net::scan_tcp(
+            probe.host,
+            probe.address,
+            probe.timeout,
+            probe.attemps,
+            probe.source,
+            probe.connector,
+            probe.cancel,
+        )
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        net::scan_udp(
+            probe.address,
+            probe.timeout,
+            probe.attemps,
+            probe.source,
+            probe.cancel,
+        )
+    }
+    fn describe(&self) -> String {
+        "sockets".to_owned()
+    }
+}
@@ -0,0 +1,292 @@
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, SocketAddr, TcpStream},
+    time::{Duration, Instant},
+};
+
+use socket2::Type;
+
+use crate::{
+    net::{self, Connector, Source},
+    Cancellation,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scheme {
+    Socks5,
+    Socks5h, // names are resolved by the proxy
+    Http,    // CONNECT, names are always resolved by the proxy
+}
+
+#[derive(Clone, Debug)]
+pub struct Proxy {
+    scheme: Scheme,
+    address: SocketAddr,
+    auth: Option<(String, String)>,
+    url: String, // as given, without the password
+}
+
+impl Proxy {
+    // `socks5://[user:password@]host:port`, `socks5h://` for remote DNS
+    // or `http://` for a CONNECT proxy. `resolve` turns `host:port` into
+    // the proxy's address
+    pub fn parse(url: &str, resolve: impl FnOnce(&str) -> Option<SocketAddr>) -> Option<Proxy> {
+        let (name, rest) = url.split_once("://")?;
+        let name = name.to_lowercase();
+        let scheme = match name.as_str() {
+            "socks5" => Scheme::Socks5,
+            "socks5h" => Scheme::Socks5h,
+            "http" => Scheme::Http,
+            _ => return None,
+        };
+        let (auth, host) = match rest.rsplit_once('@') {
+            Some((auth, host)) => {
+                let (user, password) = auth.split_once(':')?;
+                // SOCKS5 sends their length in a byte
+                if user.len() > 255 || password.len() > 255 {
+                    return None;
+                }
+                (Some((user.to_owned(), password.to_owned())), host)
+            }
+            None => (None, rest),
+        };
+        let host = host.trim_end_matches('/');
+        let address = resolve(host)?;
+        let url = match &auth {
+            Some((user, _)) => format!("{}://{}@{}", name, user, host),
+            None => format!("{}://{}", name, host),
+        };
+        Some(Proxy {
+            scheme,
+            address,
+            auth,
+            url,
+        })
+    }
+    fn socks5(&self, stream: &mut Tunnel, host: &str, address: SocketAddr) -> io::Result<()> {
+        let method = if self.auth.is_some() { 0x02 } else { 0x00 };
+        stream.write_all(&[0x05, 0x01, method])?;
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != 0x05 {
+            return Err(self.failure("does not speak SOCKS5"));
+        }
+        if reply[1] != method {
+            return Err(self.failure("refused the authentication method"));
+        }
+        if let Some((user, password)) = &self.auth {
+            let mut request = vec![0x01, user.len() as u8];
+            request.extend_from_slice(user.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request)?;
+            stream.read_exact(&mut reply)?;
+            if reply[1] != 0x00 {
+                return Err(self.failure("rejected the credentials"));
+            }
+        }
+        let mut request = vec![0x05, 0x01, 0x00];
+        match address.ip() {
+            _ if self.by_name(host) => {
+                if host.len() > 255 {
+                    return Err(net::connector_failure(format!(
+                        "host name {} is longer than 255 bytes",
+                        host
+                    )));
+                }
+                request.push(0x03);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+            IpAddr::V4(ip) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+        }
+        request.extend_from_slice(&address.port().to_be_bytes());
+        stream.write_all(&request)?;
+        stream.read_exact(&mut reply)?;
+        if reply[0] != 0x05 {
+            return Err(self.failure("does not speak SOCKS5"));
+        }
+        Err(match reply[1] {
+            0x00 => return Ok(()),
+            // denied by the proxy's rules, which may be per target
+            0x02 => io::ErrorKind::PermissionDenied,
+            0x03 => io::ErrorKind::NetworkUnreachable,
+            0x04 => io::ErrorKind::HostUnreachable,
+            0x05 => io::ErrorKind::ConnectionRefused,
+            0x06 => io::ErrorKind::TimedOut,
+            0x07 | 0x08 => return Err(self.failure("does not support the request")),
+            _ => io::ErrorKind::Other,
+        }
+        .into())
+    }
+    fn http(&self, stream: &mut Tunnel, host: &str, address: SocketAddr) -> io::Result<()> {
+        let target = if self.by_name(host) {
+            format!("{}:{}", host, address.port())
+        } else {
+            address.to_string()
+        };
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", target);
+        if let Some((user, password)) = &self.auth {
+            let credentials = base64(format!("{}:{}", user, password).as_bytes());
+            request += &format!("Proxy-Authorization: Basic {}\r\n", credentials);
+        }
+        request += "\r\n";
+        stream.write_all(request.as_bytes())?;
+        // only the status line matters, the tunnel is dropped right away
+        let mut line = Vec::new();
+        let mut byte = [0];
+        while !line.ends_with(b"\r\n") && line.len() < 1024 {
+            stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        let line = String::from_utf8_lossy(&line);
+        let status = match line.split_whitespace().nth(1).map(str::parse::<u16>) {
+            Some(Ok(status)) if line.starts_with("HTTP/") => status,
+            _ => return Err(self.failure("does not speak HTTP")),
+        };
+        Err(match status {
+            200..=299 => return Ok(()),
+            // the proxy could not connect, most proxies answer a refused
+            // connection with one of these
+            502 | 503 => io::ErrorKind::ConnectionRefused,
+            504 => io::ErrorKind::TimedOut,
+            407 => return Err(self.failure("rejected the credentials")),
+            // denied by policy, which may be per target
+            403 => io::ErrorKind::PermissionDenied,
+            _ => io::ErrorKind::Other,
+        }
+        .into())
+    }
+    // a problem with the proxy itself, not the target
+    fn failure(&self, reason: &str) -> io::Error {
+        net::connector_failure(format!("{} {}", self.address, reason))
+    }
+    // whether the target goes to the proxy as a name instead of an address
+    fn by_name(&self, host: &str) -> bool {
+        self.remote_dns() && host.parse::<IpAddr>().is_err()
+    }
+}
+
+impl Connector for Proxy {
+    fn connect(
+        &self,
+        host: &str,
+        address: SocketAddr,
+        timeout: Duration,
+        source: &Source,
+        cancel: &Cancellation,
+    ) -> io::Result<()> {
+        // the same for every probe
+        if source
+            .ip
+            .is_some_and(|ip| ip.is_ipv4() != self.address.is_ipv4())
+        {
+            return Err(self.failure("has another address family than the source ip"));
+        }
+        let socket =
+            net::socket(&self.address, Type::STREAM, source).map_err(net::socket_failure)?;
+        net::connect(&socket, &self.address, timeout, cancel).map_err(|e| {
+            // says nothing about the target
+            if cancel.is_cancelled() {
+                e
+            } else {
+                self.failure(&format!("is unreachable: {}", e))
+            }
+        })?;
+        let stream = TcpStream::from(socket);
+        stream.set_write_timeout(Some(timeout))?;
+        let mut stream = Tunnel {
+            stream,
+            deadline: Instant::now() + timeout,
+            cancel,
+        };
+        match self.scheme {
+            Scheme::Socks5 | Scheme::Socks5h => self.socks5(&mut stream, host, address),
+            Scheme::Http => self.http(&mut stream, host, address),
+        }
+        .map_err(|e| match e.kind() {
+            // hung up before answering, a timeout is left to the target
+            // as the proxy may still be waiting for it
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => self.failure("closed the connection"),
+            _ => e,
+        })
+    }
+    fn remote_dns(&self) -> bool {
+        self.scheme != Scheme::Socks5
+    }
+    fn describe(&self) -> String {
+        self.url.clone()
+    }
+}
+
+// the connection to the proxy, waiting for an answer gives up at the
+// deadline or once the probe is cancelled
+struct Tunnel<'a> {
+    stream: TcpStream,
+    deadline: Instant,
+    cancel: &'a Cancellation,
+}
+
+impl Read for Tunnel<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.cancel.is_cancelled() {
+                return Err(net::cancelled());
+            }
+            let now = Instant::now();
+            if now >= self.deadline {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.stream
+                .set_read_timeout(Some(net::SLICE.min(self.deadline - now)))?;
+            match self.stream.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                rsl => return rsl,
+            }
+        }
+    }
+}
+
+impl Write for Tunnel<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::{Address, AddressRange, Host, JobConfig, JobId, Protocol};
+
+// the ports of a range left to scan, both ends included
+struct Ports {
+    next: u32, // u32, moves past 65535 once the last port is popped
+    to: u32,
+}
+
+impl Ports {
+    // empty when `to` is below `from`
+    fn range(from: u16, to: u16) -> Ports {
+        Ports {
+            next: from as u32,
+            to: to as u32,
+        }
+    }
+    fn len(&self) -> usize {
+        (self.to + 1).saturating_sub(self.next) as usize
+    }
+    fn is_empty(&self) -> bool {
+        self.next > self.to
+    }
+    fn pop(&mut self) -> Option<u16> {
+        if self.is_empty() {
+            return None;
+        }
+        let port = self.next as u16;
+        self.next += 1;
+        Some(port)
+    }
+}
+
+// the ports of one job left to scan on one address
+struct Entry {
+    job: JobId,
+    host: Host,
+    ip: SocketAddr,
+    protocol: Protocol,
+    config: JobConfig,
+    ports: Ports,
+}
+
+// jobs of the highest priority present are served first,
+// those sharing the same priority take turns address by address
+#[derive(Default)]
+pub struct ScanQueue {
+    entries: Vec<Option<Entry>>,
+    free: Vec<usize>,                        // slots of `entries` to reuse
+    turns: BTreeMap<usize, VecDeque<usize>>, // entries by priority
+}
+
+impl ScanQueue {
+    fn entry(&self, idx: usize) -> &Entry {
+        self.entries[idx]
+            .as_ref()
+            .expect("FATAL: Scan queue refers to a removed entry.")
+    }
+    // only jobs whose address is accepted by `available` are considered
+    pub fn pop<F: Fn(&str, IpAddr) -> bool>(
+        &mut self,
+        available: F,
+    ) -> Option<(JobId, Address, JobConfig)> {
+        let (priority, pos) = self.turns.iter().rev().find_map(|(priority, turn)| {
+            let pos = turn.iter().position(|&idx| {
+                let entry = self.entry(idx);
+                available(&entry.host, entry.ip.ip())
+            })?;
+            Some((*priority, pos))
+        })?;
+        let turn = self.turns.get_mut(&priority)?;
+        let idx = turn.remove(pos)?;
+        let entry = self.entries[idx].as_mut()?;
+        let port = entry.ports.pop()?;
+        let mut ip = entry.ip;
+        ip.set_port(port);
+        let item = (
+            entry.job,
+            (entry.host.clone(), ip, entry.protocol),
+            entry.config,
+        );
+        let done = entry.ports.is_empty();
+        if !done {
+            turn.push_back(idx);
+        }
+        if turn.is_empty() {
+            self.turns.remove(&priority);
+        }
+        if done {
+            self.remove(idx);
+        }
+        Some(item)
+    }
+    fn remove(&mut self, idx: usize) {
+        if self.entries[idx].take().is_some() {
+            self.free.push(idx);
+        }
+    }
+    // every job scans its own ports, even where they overlap another's.
+    // returns how many ports were queued
+    pub fn push(&mut self, range: AddressRange) -> usize {
+        let ports = Ports::range(range.from, range.to);
+        if ports.is_empty() {
+            return 0;
+        }
+        let queued = ports.len();
+        let entry = Entry {
+            job: range.job,
+            host: range.host,
+            ip: range.ip,
+            protocol: range.protocol,
+            config: range.config,
+            ports,
+        };
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.entries[idx] = Some(entry);
+                idx
+            }
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            }
+        };
+        self.turns
+            .entry(range.config.priority)
+            .or_default()
+            .push_back(idx);
+        queued
+    }
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+    pub fn clear(&mut self) {
+        *self = ScanQueue::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+
+    fn address(last: u8) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, last), 0))
+    }
+
+    fn range(job: JobId, host: &str, ip: SocketAddr, from: u16, to: u16) -> AddressRange {
+        AddressRange {
+            job,
+            host: host.into(),
+            ip,
+            protocol: Protocol::Tcp,
+            from,
+            to,
+            config: JobConfig::default(),
+        }
+    }
+
+    fn drain(queue: &mut ScanQueue) -> Vec<(JobId, String, u16)> {
+        let mut popped = vec![];
+        while let Some((job, (host, ip, _), _)) = queue.pop(|_, _| true) {
+            popped.push((job, host.to_string(), ip.port()));
+        }
+        popped
+    }
+
+    #[test]
+    fn whole_port_range() {
+        let mut set = Ports::range(0, 65535);
+        assert_eq!(set.len(), 65536);
+        let mut expected = 0u32;
+        while let Some(port) = set.pop() {
+            assert_eq!(port as u32, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 65536);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn reversed_range_is_empty() {
+        assert!(Ports::range(10, 9).is_empty());
+        assert_eq!(Ports::range(10, 9).len(), 0);
+    }
+
+    #[test]
+    fn pop_serves_every_port_once() {
+        let mut queue = ScanQueue::default();
+        assert_eq!(queue.push(range(1, "a", address(1), 1, 3)), 3);
+        assert_eq!(queue.push(range(1, "a", address(2), 1, 2)), 2);
+        let popped = drain(&mut queue);
+        // the two addresses take turns
+        let ports = popped.iter().map(|p| p.2).collect::<Vec<_>>();
+        assert_eq!(ports, [1, 1, 2, 2, 3]);
+        assert!(queue.is_empty());
+        assert!(queue.pop(|_, _| true).is_none());
+    }
+
+    #[test]
+    fn jobs_scan_their_own_ports() {
+        let mut queue = ScanQueue::default();
+        assert_eq!(queue.push(range(1, "a", address(1), 1, 10)), 10);
+        assert_eq!(queue.push(range(2, "a", address(1), 1, 10)), 10);
+        let popped = drain(&mut queue);
+        assert_eq!(popped.iter().filter(|p| p.0 == 1).count(), 10);
+        assert_eq!(popped.iter().filter(|p| p.0 == 2).count(), 10);
+    }
+
+    #[test]
+    fn names_sharing_the_unspecified_address_are_kept_apart() {
+        let unspecified = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0));
+        let mut queue = ScanQueue::default();
+        assert_eq!(queue.push(range(1, "a.example", unspecified, 1, 5)), 5);
+        assert_eq!(queue.push(range(1, "b.example", unspecified, 1, 5)), 5);
+        let popped = drain(&mut queue);
+        assert_eq!(popped.iter().filter(|p| p.1 == "a.example").count(), 5);
+        assert_eq!(popped.iter().filter(|p| p.1 == "b.example").count(), 5);
+    }
+
+    #[test]
+    fn higher_priority_first() {
+        let mut queue = ScanQueue::default();
+        queue.push(range(1, "a", address(1), 1, 2));
+        let mut urgent = range(2, "a", address(2), 1, 2);
+        urgent.config = JobConfig::default().priority(5);
+        queue.push(urgent);
+        let jobs = drain(&mut queue).iter().map(|p| p.0).collect::<Vec<_>>();
+        assert_eq!(jobs, [2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn pop_skips_unavailable_addresses() {
+        let mut queue = ScanQueue::default();
+        queue.push(range(1, "a", address(1), 1, 2));
+        queue.push(range(2, "b", address(2), 1, 2));
+        let (job, (host, ..), _) = queue.pop(|host, _| host == "b").unwrap();
+        assert_eq!((job, &*host), (2, "b"));
+        assert!(queue.pop(|_, _| false).is_none());
+        assert_eq!(drain(&mut queue).len(), 3);
+    }
+
+    #[test]
+    fn slots_are_reused() {
+        let mut queue = ScanQueue::default();
+        queue.push(range(1, "a", address(1), 1, 1));
+        queue.push(range(2, "a", address(2), 1, 1));
+        assert_eq!(drain(&mut queue).len(), 2);
+        assert_eq!(queue.free.len(), 2);
+        queue.push(range(3, "a", address(3), 1, 4));
+        assert_eq!(queue.entries.len(), 2);
+        assert_eq!(queue.free.len(), 1);
+        assert_eq!(drain(&mut queue).len(), 4);
+    }
+}
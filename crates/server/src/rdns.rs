@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+fn ptr_lookup(ip: IpAddr) -> Option<String> {
+    use std::mem::{size_of, zeroed};
+    let mut host = [0 as libc::c_char; libc::NI_MAXHOST as usize];
+    let rsl = unsafe {
+        match ip {
+            IpAddr::V4(ip) => {
+                let mut sin: libc::sockaddr_in = zeroed();
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_addr.s_addr = u32::from_ne_bytes(ip.octets());
+                libc::getnameinfo(
+                    &sin as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+            IpAddr::V6(ip) => {
+                let mut sin6: libc::sockaddr_in6 = zeroed();
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_addr.s6_addr = ip.octets();
+                libc::getnameinfo(
+                    &sin6 as *const _ as *const libc::sockaddr,
+                    size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+    };
+    if rsl != 0 {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(host.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn ptr_lookup(_ip: IpAddr) -> Option<String> {
+    None
+}
+
+enum Name {
+    Queued, // waits for a free lookup slot
+    Pending,
+    Done(Option<String>),
+}
+
+#[derive(Default)]
+struct State {
+    names: HashMap<IpAddr, Name>,
+    waiting: VecDeque<IpAddr>,
+    running: Vec<(IpAddr, Instant)>, // lookups holding a slot
+    concurrency: usize,
+}
+
+// PTR lookups shared by the whole scanner. every address is looked up
+// once, at most `concurrency` lookups hold a slot at the same time
+pub struct ReverseDns {
+    state: Mutex<State>,
+    lookup: fn(IpAddr) -> Option<String>,
+}
+
+impl Default for ReverseDns {
+    fn default() -> Self {
+        ReverseDns {
+            state: Mutex::default(),
+            lookup: ptr_lookup,
+        }
+    }
+}
+
+impl ReverseDns {
+    // the name of `ip` once its lookup is done. otherwise the lookup is
+    // started or queued and `None` returned right away, nothing waits
+    // for a name. a lookup taking longer than `timeout` gives its slot
+    // to the next address, its answer still names later results
+    pub fn name(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        timeout: Duration,
+        concurrency: usize,
+    ) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        state.concurrency = concurrency.max(1);
+        state.running.retain(|(_, start)| start.elapsed() < timeout);
+        match state.names.get(&ip) {
+            Some(Name::Done(name)) => return name.clone(),
+            Some(_) => {}
+            None => {
+                state.names.insert(ip, Name::Queued);
+                state.waiting.push_back(ip);
+            }
+        }
+        self.fill(&mut state);
+        None
+    }
+    // starts queued lookups while there are free slots
+    fn fill(self: &Arc<Self>, state: &mut State) {
+        while state.running.len() < state.concurrency {
+            let ip = match state.waiting.pop_front() {
+                Some(ip) => ip,
+                None => return,
+            };
+            let rdns = self.clone();
+            let lookup = self.lookup;
+            let started = std::thread::Builder::new()
+                .spawn(move || {
+                    let name = lookup(ip);
+                    let mut state = rdns.state.lock().unwrap();
+                    state.names.insert(ip, Name::Done(name));
+                    state.running.retain(|(running, _)| *running != ip);
+                    rdns.fill(&mut state);
+                })
+                .is_ok();
+            if started {
+                state.names.insert(ip, Name::Pending);
+                state.running.push((ip, Instant::now()));
+            } else {
+                state.names.insert(ip, Name::Done(None));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, thread::sleep};
+
+    use super::*;
+
+    const LATENCY: Duration = Duration::from_millis(300);
+
+    fn slow(ip: IpAddr) -> Option<String> {
+        sleep(LATENCY);
+        Some(format!("host-{}", ip))
+    }
+
+    fn rdns() -> Arc<ReverseDns> {
+        Arc::new(ReverseDns {
+            state: Mutex::default(),
+            lookup: slow,
+        })
+    }
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn naming_does_not_wait_for_lookups() {
+        let rdns = rdns();
+        let start = Instant::now();
+        for last in 0..=255 {
+            assert_eq!(rdns.name(ip(last), Duration::from_secs(5), 2), None);
+        }
+        // every result would have waited for a lookup before
+        assert!(start.elapsed() < LATENCY);
+    }
+
+    #[test]
+    fn finished_lookups_name_later_results() {
+        let rdns = rdns();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(rdns.name(ip(1), timeout, 1), None);
+        sleep(LATENCY * 2);
+        assert_eq!(rdns.name(ip(1), timeout, 1), Some("host-10.0.0.1".into()));
+    }
+
+    #[test]
+    fn slow_lookups_give_up_their_slot() {
+        let rdns = rdns();
+        let timeout = Duration::from_millis(50);
+        rdns.name(ip(1), timeout, 1);
+        rdns.name(ip(2), timeout, 1);
+        sleep(timeout * 2);
+        // the first lookup timed out, the second one takes its slot
+        rdns.name(ip(3), timeout, 1);
+        let state = rdns.state.lock().unwrap();
+        assert!(matches!(state.names.get(&ip(2)), Some(Name::Pending)));
+        assert!(matches!(state.names.get(&ip(3)), Some(Name::Queued)));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::{net, Cancellation, Outcome, PortState, Probe, Prober, Protocol};
+
+struct ClockState {
+    now: Duration,
+    manual: bool,
+}
+
+// time as seen by a `SimulatedNetwork`, probes never sleep for real.
+// an automatic clock jumps forward to the end of every probe, a manual
+// one holds each probe until `advance` moves it past the probe's end
+#[derive(Clone)]
+pub struct VirtualClock {
+    state: Arc<(Mutex<ClockState>, Condvar)>,
+}
+
+impl VirtualClock {
+    fn with_mode(manual: bool) -> VirtualClock {
+        let state = ClockState {
+            now: Duration::ZERO,
+            manual,
+        };
+        VirtualClock {
+            state: Arc::new((Mutex::new(state), Condvar::new())),
+        }
+    }
+    pub fn automatic() -> VirtualClock {
+        VirtualClock::with_mode(false)
+    }
+    pub fn manual() -> VirtualClock {
+        VirtualClock::with_mode(true)
+    }
+    pub fn now(&self) -> Duration {
+        self.state.0.lock().unwrap().now
+    }
+    pub fn advance(&self, by: Duration) {
+        let (state, changed) = &*self.state;
+        state.lock().unwrap().now += by;
+        changed.notify_all();
+    }
+    // returns once the clock has reached `now() + duration`, or right away
+    // when cancelled
+    fn elapse(&self, duration: Duration, cancel: &Cancellation) {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        let end = state.now + duration;
+        if !state.manual {
+            state.now = state.now.max(end);
+            return;
+        }
+        while state.now < end && !cancel.is_cancelled() {
+            state = changed.wait_timeout(state, net::SLICE).unwrap().0;
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::automatic()
+    }
+}
+
+// a declarative network for running a `Scanner` without sockets:
+// host → port → state and the time it takes to answer.
+// unreachable ports never answer and cost the whole timeout,
+// over UDP they are reported closed like with real sockets
+#[derive(Default)]
+pub struct SimulatedNetwork {
+    ports: HashMap<(IpAddr, Protocol, u16), (PortState, Duration)>,
+    default: Option<(PortState, Duration)>, // closed and instant when unset
+    clock: VirtualClock,
+}
+
+impl SimulatedNetwork {
+    pub fn port(self, ip: IpAddr, protocol: Protocol, port: u16, state: PortState) -> Self {
+        self.port_with_latency(ip, protocol, port, state, Duration::ZERO)
+    }
+    pub fn port_with_latency(
+        self,
+        ip: IpAddr,
+        protocol: Protocol,
+        port: u16,
+        state: PortState,
+        latency: Duration,
+    ) -> Self {
+        let mut s = self;
+        s.ports.insert((ip, protocol, port), (state, latency));
+        s
+    }
+    // every port that is not listed
+    pub fn default_state(self, state: PortState, latency: Duration) -> Self {
+        let mut s = self;
+        s.default = Some((state, latency));
+        s
+    }
+    pub fn clock(self, clock: VirtualClock) -> Self {
+        let mut s = self;
+        s.clock = clock;
+        s
+    }
+    fn probe(&self, protocol: Protocol, probe: &Probe) -> Outcome {
+        let key = (probe.address.ip(), protocol, probe.address.port());
+        let (state, latency) = self
+            .ports
+            .get(&key)
+            .or(self.default.as_ref())
+            .unwrap_or(&(PortState::Closed, Duration::ZERO));
+        let (state, error) = match state {
+            // like `net::try_udp`, a UDP port that never answers counts as closed
+            _ if *latency > probe.timeout || *state == PortState::Unreachable => match protocol {
+                Protocol::Tcp => (PortState::Unreachable, Some(io::ErrorKind::TimedOut)),
+                Protocol::Udp => (PortState::Closed, Some(io::ErrorKind::TimedOut)),
+            },
+            PortState::Open => (PortState::Open, None),
+            _ => (PortState::Closed, Some(io::ErrorKind::ConnectionRefused)),
+        };
+        // same retry rules as the socket prober, only an open port
+        // stops early
+        let attemps = match state {
+            PortState::Open => probe.attemps.min(1),
+            _ => probe.attemps,
+        };
+        if attemps == 0 {
+            return Outcome::default();
+        }
+        let answered = error != Some(io::ErrorKind::TimedOut);
+        let per_attempt = if answered { *latency } else { probe.timeout };
+        self.clock
+            .elapse(per_attempt * attemps as u32, probe.cancel);
+        Outcome {
+            state,
+            attemps,
+            latency: answered.then_some(*latency),
+            error,
+            failure: None,
+        }
+    }
+}
+
+impl Prober for SimulatedNetwork {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        self.probe(Protocol::Tcp, probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        self.probe(Protocol::Udp, probe)
+    }
+    fn describe(&self) -> String {
+        "simulated".to_owned()
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use server::{Error, Input, JobConfig, Output, PortState, Scanner};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+// a proxy on a local port, every connection is handed to `serve` together
+// with a channel for what it wants the test to check
+fn stand_in(serve: fn(TcpStream, &Sender<Vec<u8>>)) -> (SocketAddr, Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let tx = tx.clone();
+            std::thread::spawn(move || serve(stream.unwrap(), &tx));
+        }
+    });
+    (address, rx)
+}
+
+fn scanner() -> (Scanner, Receiver<Output>) {
+    let (tx, rx) = mpsc::channel();
+    let scanner = Scanner::new(move |output| {
+        let _ = tx.send(output);
+    });
+    (scanner, rx)
+}
+
+// the targets are never reached, the stand-in answers for them
+fn scan(url: String, from: u16, to: u16) -> Vec<Output> {
+    let (scanner, rx) = scanner();
+    scanner.command(Input::Proxy(Some(url))).unwrap();
+    let input = Input::TcpRange("192.0.2.1".to_owned(), from, to, JobConfig::default());
+    scanner.command(input).unwrap();
+    let mut outputs = vec![];
+    loop {
+        match rx.recv_timeout(WAIT).expect("scanner never became idle") {
+            Output::Idle => return outputs,
+            output => outputs.push(output),
+        }
+    }
+}
+
+fn states(outputs: &[Output]) -> Vec<(u16, PortState)> {
+    let mut states = outputs
+        .iter()
+        .filter_map(|output| match output {
+            Output::TcpScan(result) => Some((result.address.port(), result.state)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    states.sort_unstable_by_key(|(port, _)| *port);
+    states
+}
+
+fn proxy_error(outputs: &[Output]) -> bool {
+    outputs
+        .iter()
+        .any(|output| matches!(output, Output::Error(Error::Proxy(_))))
+}
+
+fn cancelled(outputs: &[Output]) -> bool {
+    outputs
+        .iter()
+        .any(|output| matches!(output, Output::JobCancelled(_)))
+}
+
+fn read(stream: &mut TcpStream, count: usize) -> Vec<u8> {
+    let mut buffer = vec![0; count];
+    stream.read_exact(&mut buffer).unwrap();
+    buffer
+}
+
+// answers the CONNECT request for port `n` with reply code `n - 1`
+fn socks5(mut stream: TcpStream, tx: &Sender<Vec<u8>>) {
+    let greeting = read(&mut stream, 3);
+    tx.send(greeting).unwrap();
+    stream.write_all(&[0x05, 0x02]).unwrap();
+    let mut auth = read(&mut stream, 2);
+    auth.extend(read(&mut stream, auth[1] as usize + 1));
+    let length = *auth.last().unwrap() as usize;
+    auth.extend(read(&mut stream, length));
+    tx.send(auth).unwrap();
+    stream.write_all(&[0x01, 0x00]).unwrap();
+    let request = read(&mut stream, 10);
+    assert_eq!(request[..8], [0x05, 0x01, 0x00, 0x01, 192, 0, 2, 1]);
+    let port = u16::from_be_bytes([request[8], request[9]]);
+    let reply = [0x00, 0x04, 0x05, 0x06][port as usize - 1];
+    stream
+        .write_all(&[0x05, reply, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .unwrap();
+}
+
+#[test]
+fn socks5_greets_and_authenticates() {
+    let (address, rx) = stand_in(socks5);
+    scan(format!("socks5://user:secret@{}", address), 1, 1);
+    // version 5, one method, username/password
+    assert_eq!(rx.recv_timeout(WAIT).unwrap(), [0x05, 0x01, 0x02]);
+    let mut auth = vec![0x01, 4];
+    auth.extend_from_slice(b"user");
+    auth.push(6);
+    auth.extend_from_slice(b"secret");
+    assert_eq!(rx.recv_timeout(WAIT).unwrap(), auth);
+}
+
+#[test]
+fn socks5_reply_codes_give_port_states() {
+    let (address, _rx) = stand_in(socks5);
+    let outputs = scan(format!("socks5://user:secret@{}", address), 1, 4);
+    assert!(!proxy_error(&outputs));
+    assert_eq!(
+        states(&outputs),
+        [
+            (1, PortState::Open),        // succeeded
+            (2, PortState::Unreachable), // host unreachable
+            (3, PortState::Closed),      // connection refused
+            (4, PortState::Unreachable), // TTL expired
+        ]
+    );
+}
+
+#[test]
+fn socks5_proxy_hanging_up_cancels_the_scan() {
+    let (address, _rx) = stand_in(|stream, _| drop(stream));
+    let outputs = scan(format!("socks5://{}", address), 1, 4);
+    assert!(proxy_error(&outputs));
+    assert!(cancelled(&outputs));
+    assert!(states(&outputs).is_empty());
+}
+
+#[test]
+fn unusable_proxy_urls_are_refused() {
+    let (scanner, _rx) = scanner();
+    for url in [
+        "ftp://127.0.0.1:21",
+        "socks5://127.0.0.1",
+        "socks5://host.invalid:1080",
+    ] {
+        assert_eq!(
+            scanner.command(Input::Proxy(Some(url.to_owned()))),
+            Err(Error::Proxy(format!("invalid url {}", url)))
+        );
+    }
+}
+
+// the request head, up to the empty line
+fn request_head(stream: &mut TcpStream) -> String {
+    let mut head = vec![];
+    while !head.ends_with(b"\r\n\r\n") {
+        head.extend(read(stream, 1));
+    }
+    String::from_utf8(head).unwrap()
+}
+
+// answers the CONNECT request for port `n` with the `n`th status line
+fn http(mut stream: TcpStream, tx: &Sender<Vec<u8>>) {
+    let head = request_head(&mut stream);
+    tx.send(head.clone().into_bytes()).unwrap();
+    let target = head.split_whitespace().nth(1).unwrap();
+    let port = target.rsplit_once(':').unwrap().1.parse::<usize>().unwrap();
+    let status = [
+        "HTTP/1.1 200 Connection established",
+        "HTTP/1.1 403 Forbidden",
+        "HTTP/1.0 502 Bad Gateway",
+        "HTTP/1.1 503 Service Unavailable",
+        "HTTP/1.1 504 Gateway Timeout",
+        "HTTP/1.1 407 Proxy Authentication Required",
+        "SSH-2.0-OpenSSH_9.6",
+    ][port - 1];
+    stream
+        .write_all(format!("{}\r\n\r\n", status).as_bytes())
+        .unwrap();
+}
+
+#[test]
+fn http_connect_statuses_give_port_states() {
+    let (address, _rx) = stand_in(http);
+    let outputs = scan(format!("http://{}", address), 1, 5);
+    assert!(!proxy_error(&outputs));
+    assert_eq!(
+        states(&outputs),
+        [
+            (1, PortState::Open),
+            (2, PortState::Unreachable), // denied by the proxy
+            (3, PortState::Closed),      // the proxy was refused
+            (4, PortState::Closed),
+            (5, PortState::Unreachable), // the proxy timed out
+        ]
+    );
+}
+
+#[test]
+fn http_proxy_asking_for_credentials_cancels_the_scan() {
+    let (address, _rx) = stand_in(http);
+    let outputs = scan(format!("http://{}", address), 6, 6);
+    assert!(proxy_error(&outputs));
+    assert!(cancelled(&outputs));
+}
+
+#[test]
+fn http_proxy_not_speaking_http_cancels_the_scan() {
+    let (address, _rx) = stand_in(http);
+    let outputs = scan(format!("http://{}", address), 7, 7);
+    assert!(proxy_error(&outputs));
+    assert!(cancelled(&outputs));
+}
+
+#[test]
+fn http_credentials_are_sent_in_base64() {
+    let (address, rx) = stand_in(http);
+    for (credentials, encoded) in [
+        ("u:p", "dTpw"),                     // no padding
+        ("us:pw", "dXM6cHc="),               // one padding character
+        ("user:secret", "dXNlcjpzZWNyZXQ="), // the same
+        ("a:b>?~?", "YTpiPj9+Pw=="),         // two, and `+`
+        ("u:???", "dTo/Pz8="),               // `/`
+    ] {
+        scan(format!("http://{}@{}", credentials, address), 1, 1);
+        let head = String::from_utf8(rx.recv_timeout(WAIT).unwrap()).unwrap();
+        assert!(head.starts_with("CONNECT 192.0.2.1:1 HTTP/1.1\r\n"));
+        assert!(
+            head.contains(&format!("\r\nProxy-Authorization: Basic {}\r\n", encoded)),
+            "{}",
+            head
+        );
+    }
+}
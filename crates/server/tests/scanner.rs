@@ -0,0 +1,466 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    time::Duration,
+};
+
+use server::{
+    socket_failure, Cancellation, Connector, Error, Input, JobConfig, Outcome, Output, PortState,
+    Probe, Prober, Protocol, Scanner, SimulatedNetwork, Source, VirtualClock,
+};
+
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const WAIT: Duration = Duration::from_secs(10);
+
+fn scanner<P: Prober + 'static>(prober: P) -> (Scanner, Receiver<Output>) {
+    let (tx, rx) = mpsc::channel();
+    let scanner = Scanner::with_prober(prober, move |output| {
+        let _ = tx.send(output);
+    });
+    (scanner, rx)
+}
+
+fn tcp(from: u16, to: u16) -> Input {
+    Input::TcpRange("127.0.0.1".to_owned(), from, to, JobConfig::default())
+}
+
+// everything up to the next `Idle`
+fn until_idle(rx: &Receiver<Output>) -> Vec<Output> {
+    let mut outputs = vec![];
+    loop {
+        match rx.recv_timeout(WAIT).expect("scanner never became idle") {
+            Output::Idle => return outputs,
+            output => outputs.push(output),
+        }
+    }
+}
+
+fn until(rx: &Receiver<Output>, wanted: impl Fn(&Output) -> bool) -> Vec<Output> {
+    let mut outputs = vec![];
+    loop {
+        let output = rx.recv_timeout(WAIT).expect("output never came");
+        let done = wanted(&output);
+        outputs.push(output);
+        if done {
+            return outputs;
+        }
+    }
+}
+
+fn ports(outputs: &[Output], state: PortState) -> Vec<u16> {
+    let mut ports = outputs
+        .iter()
+        .filter_map(|output| match output {
+            Output::TcpScan(result) | Output::UdpScan(result) if result.state == state => {
+                Some(result.address.port())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    ports.sort_unstable();
+    ports
+}
+
+fn scans(outputs: &[Output]) -> usize {
+    outputs
+        .iter()
+        .filter(|output| matches!(output, Output::TcpScan(_) | Output::UdpScan(_)))
+        .count()
+}
+
+// a simulated network that counts the probes running at once
+#[derive(Default)]
+struct Counting {
+    network: SimulatedNetwork,
+    running: AtomicUsize,
+    most: AtomicUsize,
+}
+
+impl Prober for Counting {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most.fetch_max(running, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(2));
+        let outcome = self.network.tcp(probe);
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        outcome
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        self.network.udp(probe)
+    }
+}
+
+struct PanicOn(u16);
+
+impl Prober for PanicOn {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        if probe.address.port() == self.0 {
+            panic!("probe of port {}", self.0);
+        }
+        SimulatedNetwork::default().tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        SimulatedNetwork::default().udp(probe)
+    }
+}
+
+#[test]
+fn reports_the_simulated_ports() {
+    let network = SimulatedNetwork::default()
+        .port(LOCALHOST, Protocol::Tcp, 2, PortState::Open)
+        .port(LOCALHOST, Protocol::Tcp, 4, PortState::Unreachable)
+        .port(LOCALHOST, Protocol::Udp, 3, PortState::Open)
+        .port(LOCALHOST, Protocol::Udp, 4, PortState::Unreachable);
+    let (scanner, rx) = scanner(network);
+    scanner.command(tcp(1, 5)).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(ports(&outputs, PortState::Open), [2]);
+    assert_eq!(ports(&outputs, PortState::Closed), [1, 3, 5]);
+    assert_eq!(ports(&outputs, PortState::Unreachable), [4]);
+    let summary = outputs.iter().find_map(|output| match output {
+        Output::JobCompleted(summary) => Some(summary.clone()),
+        _ => None,
+    });
+    let summary = summary.expect("job never completed");
+    assert_eq!(
+        (summary.open, summary.closed, summary.unreachable),
+        (1, 3, 1)
+    );
+
+    // a UDP port that never answers is closed, as with real sockets
+    let udp = Input::UdpRange("127.0.0.1".to_owned(), 3, 4, JobConfig::default());
+    scanner.command(udp).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(ports(&outputs, PortState::Open), [3]);
+    assert_eq!(ports(&outputs, PortState::Closed), [4]);
+}
+
+#[test]
+fn pause_holds_probes_until_resumed() {
+    let (scanner, rx) = scanner(SimulatedNetwork::default());
+    scanner.command(Input::Stop).unwrap();
+    scanner.command(tcp(1, 20)).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    let held = rx.try_iter().collect::<Vec<_>>();
+    assert!(held.contains(&Output::Paused));
+    assert_eq!(scans(&held), 0);
+    assert!(!held.contains(&Output::Idle));
+
+    scanner.command(Input::Cont).unwrap();
+    let outputs = until_idle(&rx);
+    assert!(outputs.contains(&Output::Resumed));
+    assert_eq!(
+        ports(&outputs, PortState::Closed),
+        (1..=20).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn cancel_drops_the_queued_probes() {
+    // probes last until the clock is moved, which it never is
+    let network = SimulatedNetwork::default()
+        .default_state(PortState::Open, Duration::from_millis(100))
+        .port_with_latency(
+            LOCALHOST,
+            Protocol::Tcp,
+            9000,
+            PortState::Open,
+            Duration::ZERO,
+        )
+        .clock(VirtualClock::manual());
+    let (scanner, rx) = scanner(network);
+    scanner.command(Input::TcpTimeout(1000)).unwrap();
+    scanner.command(tcp(1, 1000)).unwrap();
+    until(&rx, |output| matches!(output, Output::JobStarted(_)));
+
+    scanner.command(Input::Cancel).unwrap();
+    let outputs = until_idle(&rx);
+    let cancelled = outputs.iter().find_map(|output| match output {
+        Output::JobCancelled(summary) => Some(summary.clone()),
+        _ => None,
+    });
+    assert!(cancelled.is_some());
+    assert_eq!(scans(&outputs), 0);
+
+    // the scanner is still usable
+    scanner.command(tcp(9000, 9000)).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(ports(&outputs, PortState::Open), [9000]);
+}
+
+#[test]
+fn thread_count_limits_the_probes_in_flight() {
+    let prober = Arc::new(Counting::default());
+    let (scanner, rx) = scanner(prober.clone());
+    assert_eq!(scanner.threads(8).unwrap(), 8);
+    scanner.command(tcp(1, 200)).unwrap();
+    assert_eq!(scans(&until_idle(&rx)), 200);
+    assert!(prober.most.load(Ordering::SeqCst) > 1);
+
+    assert_eq!(scanner.threads(1).unwrap(), 1);
+    prober.most.store(0, Ordering::SeqCst);
+    scanner.command(tcp(1, 50)).unwrap();
+    assert_eq!(scans(&until_idle(&rx)), 50);
+    assert_eq!(prober.most.load(Ordering::SeqCst), 1);
+
+    assert_eq!(scanner.threads(4).unwrap(), 4);
+    scanner.command(tcp(1, 100)).unwrap();
+    assert_eq!(scans(&until_idle(&rx)), 100);
+}
+
+#[test]
+fn shrinking_while_busy_loses_no_probe() {
+    let prober = Arc::new(Counting::default());
+    let (scanner, rx) = scanner(prober.clone());
+    scanner.threads(8).unwrap();
+    scanner.command(tcp(1, 500)).unwrap();
+    until(&rx, |output| matches!(output, Output::JobStarted(_)));
+    assert_eq!(scanner.threads(2).unwrap(), 2);
+    let outputs = until_idle(&rx);
+    assert!(prober.most.load(Ordering::SeqCst) <= 8);
+    assert!(outputs.iter().any(|output| matches!(
+        output,
+        Output::JobCompleted(summary) if summary.closed == 500
+    )));
+}
+
+#[test]
+fn too_many_threads_are_refused() {
+    let (scanner, _rx) = scanner(SimulatedNetwork::default());
+    assert!(matches!(
+        scanner.threads(usize::MAX),
+        Err(Error::TooManyThreads(..))
+    ));
+}
+
+// probes wait while `hold` is set
+#[derive(Default)]
+struct Held {
+    hold: AtomicBool,
+    running: AtomicUsize,
+}
+
+impl Prober for Held {
+    fn tcp(&self, probe: &Probe) -> Outcome {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        while self.hold.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        SimulatedNetwork::default().tcp(probe)
+    }
+    fn udp(&self, probe: &Probe) -> Outcome {
+        SimulatedNetwork::default().udp(probe)
+    }
+}
+
+#[test]
+fn retiring_workers_count_against_the_thread_limit() {
+    let prober = Arc::new(Held::default());
+    prober.hold.store(true, Ordering::SeqCst);
+    let (scanner, rx) = scanner(prober.clone());
+    let max = match scanner.threads(usize::MAX) {
+        Err(Error::TooManyThreads(_, max)) => max,
+        other => panic!("{:?}", other),
+    };
+    scanner.threads(4).unwrap();
+    scanner.command(tcp(1, 4)).unwrap();
+    while prober.running.load(Ordering::SeqCst) < 4 {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // three workers leave the pool, their threads live on until released
+    assert_eq!(scanner.threads(1), Ok(1));
+    assert_eq!(
+        scanner.threads(max - 2),
+        Err(Error::TooManyThreads(max - 2, max - 3))
+    );
+    prober.hold.store(false, Ordering::SeqCst);
+    until_idle(&rx);
+}
+
+#[test]
+fn worker_panic_fails_only_its_probe() {
+    let (scanner, rx) = scanner(PanicOn(3));
+    scanner.threads(2).unwrap();
+    scanner.command(tcp(1, 5)).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(ports(&outputs, PortState::Closed), [1, 2, 4, 5]);
+    assert!(outputs.iter().any(|output| matches!(
+        output,
+        Output::Error(Error::ProbeFailed(Protocol::Tcp, _, address)) if address.port() == 3
+    )));
+    assert!(outputs.iter().any(|output| matches!(
+        output,
+        Output::JobCompleted(summary) if summary.failed == 1 && summary.closed == 4
+    )));
+
+    // the lost worker was replaced
+    scanner.command(tcp(10, 20)).unwrap();
+    assert_eq!(scans(&until_idle(&rx)), 11);
+}
+
+#[test]
+fn shutdown_aborts_probes_past_the_deadline() {
+    let network = SimulatedNetwork::default()
+        .default_state(PortState::Open, Duration::from_millis(100))
+        .clock(VirtualClock::manual());
+    let (scanner, rx) = scanner(network);
+    scanner.command(Input::TcpTimeout(1000)).unwrap();
+    scanner.command(tcp(1, 100)).unwrap();
+    until(&rx, |output| matches!(output, Output::JobStarted(_)));
+
+    scanner.shutdown(20).unwrap();
+    assert_eq!(scanner.command(Input::Ping), Err(Error::Terminated));
+    let outputs = rx.try_iter().collect::<Vec<_>>();
+    assert!(outputs
+        .iter()
+        .any(|output| matches!(output, Output::JobCancelled(_))));
+}
+
+#[test]
+fn shutdown_lets_quick_probes_finish() {
+    let clock = VirtualClock::manual();
+    let network = SimulatedNetwork::default()
+        .default_state(PortState::Open, Duration::from_millis(100))
+        .clock(clock.clone());
+    let (scanner, rx) = scanner(network);
+    scanner.threads(4).unwrap();
+    scanner.command(Input::TcpTimeout(1000)).unwrap();
+    scanner.command(tcp(1, 100)).unwrap();
+    until(&rx, |output| matches!(output, Output::JobStarted(_)));
+
+    let advancing = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        clock.advance(Duration::from_secs(1));
+    });
+    scanner.shutdown(5000).unwrap();
+    advancing.join().unwrap();
+    // the probes in flight were reported, nothing else was started
+    let outputs = rx.try_iter().collect::<Vec<_>>();
+    let reported = scans(&outputs);
+    assert!((1..=4).contains(&reported), "{} probes reported", reported);
+}
+
+#[test]
+fn overlapping_jobs_are_both_scanned() {
+    let (scanner, rx) = scanner(SimulatedNetwork::default());
+    scanner.command(Input::Stop).unwrap();
+    scanner.command(tcp(1, 10)).unwrap();
+    scanner.command(tcp(5, 10)).unwrap();
+    scanner.command(Input::Cont).unwrap();
+    let outputs = until_idle(&rx);
+    let mut closed = outputs
+        .iter()
+        .filter_map(|output| match output {
+            Output::JobCompleted(summary) => Some(summary.closed),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    closed.sort_unstable();
+    assert_eq!(closed, [6, 10]);
+    // every job probes its own ports, 5-10 twice
+    assert_eq!(scans(&outputs), 16);
+}
+
+// hands names over unresolved, as a SOCKS5h or HTTP proxy does
+struct RemoteDns;
+
+impl Connector for RemoteDns {
+    fn connect(
+        &self,
+        _host: &str,
+        _address: SocketAddr,
+        _timeout: Duration,
+        _source: &Source,
+        _cancel: &Cancellation,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+    fn remote_dns(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn host_limit_tells_proxied_names_apart() {
+    let prober = Arc::new(Counting::default());
+    let (scanner, rx) = scanner(prober.clone());
+    scanner.threads(8).unwrap();
+    scanner
+        .command(Input::Connector(Arc::new(RemoteDns)))
+        .unwrap();
+    scanner.command(Input::HostLimit(1)).unwrap();
+    scanner.command(Input::SubnetLimit(1)).unwrap();
+    scanner.command(Input::Stop).unwrap();
+    for host in ["a.invalid", "b.invalid"] {
+        let input = Input::TcpRange(host.to_owned(), 1, 50, JobConfig::default());
+        scanner.command(input).unwrap();
+    }
+    scanner.command(Input::Cont).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(scans(&outputs), 100);
+    // one probe per name at a time, the shared unspecified address
+    // does not make them one host or one subnet
+    assert_eq!(prober.most.load(Ordering::SeqCst), 2);
+}
+
+// as if the process ran out of file descriptors
+struct NoSocket;
+
+impl Connector for NoSocket {
+    fn connect(
+        &self,
+        _host: &str,
+        _address: SocketAddr,
+        _timeout: Duration,
+        _source: &Source,
+        _cancel: &Cancellation,
+    ) -> io::Result<()> {
+        Err(socket_failure(io::Error::from_raw_os_error(libc::EMFILE)))
+    }
+}
+
+#[test]
+fn local_socket_errors_fail_the_probe() {
+    let (scanner, rx) = scanner(server::SocketProber);
+    scanner
+        .command(Input::Connector(Arc::new(NoSocket)))
+        .unwrap();
+    scanner.command(tcp(1, 3)).unwrap();
+    let outputs = until_idle(&rx);
+    assert_eq!(scans(&outputs), 0);
+    let errors = outputs
+        .iter()
+        .filter(|output| matches!(output, Output::Error(Error::Socket(Protocol::Tcp, ..))))
+        .count();
+    assert_eq!(errors, 3);
+    // not taken for a dead proxy, the job runs to its end
+    assert!(outputs.iter().any(|output| matches!(
+        output,
+        Output::JobCompleted(summary) if summary.failed == 3
+    )));
+}
+
+#[test]
+fn addresses_of_another_family_than_the_source_are_skipped() {
+    let (scanner, _rx) = scanner(server::SocketProber);
+    scanner.command(Input::SourceIp(Some(LOCALHOST))).unwrap();
+    let input = Input::UdpRange("::1".to_owned(), 1, 3, JobConfig::default());
+    assert_eq!(scanner.command(input), Err(Error::Family("::1".to_owned())));
+}
+
+#[test]
+fn netns_names_are_not_paths() {
+    let (scanner, _rx) = scanner(SimulatedNetwork::default());
+    for name in ["../../proc/1/ns/net", "a/b", ".", "..", ""] {
+        assert_eq!(
+            scanner.command(Input::Netns(Some(name.to_owned()))),
+            Err(Error::Netns(io::ErrorKind::InvalidInput))
+        );
+    }
+}